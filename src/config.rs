@@ -0,0 +1,794 @@
+//! Project configuration, read from `mockups.toml`.
+//!
+//! The file is looked up in the project root first and then in the `mockups`
+//! directory (next to `icon.png`). Every key is optional, missing ones fall
+//! back to the built-in defaults.
+//!
+//! ```
+//! app_name = "Awesome Mail Client"
+//! pattern  = '\A[A-Z]{2}-\[(?P<section>[\w-]+)\]-(?P<number>\d+)\.(?i:png|jpe?g|gif|webp|svg)\z'
+//! output   = "site" # or eg "../Previews/Awesome Mail Client"
+//! templates = "mockups/templates" # the default, see the `template` module
+//! theme    = "light" # or "dark", "presentation" or a directory
+//! css      = ["mockups/extra.css"]
+//! js       = ["mockups/extra.js"]
+//! strict   = false
+//! self_contained = false
+//!
+//! [thumbnails]
+//! scale     = 0.5
+//! filter    = "nearest"
+//! densities = [1, 2]
+//! preview   = 1024
+//!
+//! [lint]
+//! max_file_size = 5120 # KB
+//!
+//! [branding]
+//! company   = "Tapmates"
+//! copyright = "All rights reserved. Copyright {year} by {company}"
+//! logo      = "mockups/logo.png"
+//! favicon   = "mockups/favicon.png"
+//! accent    = "#961fd6"
+//!
+//! [[categories]]
+//! dir         = "android-portrait"
+//! name        = "Android Portrait"
+//! order       = 10
+//! family      = "android"
+//! orientation = "portrait"
+//!
+//! [categories.thumbnails]
+//! max_width  = 400
+//! max_height = 400
+//! filter     = "lanczos3"
+//!
+//! [sections]
+//! faq          = { name = "FAQ", order = 10, group = "Help" }
+//! ios-settings = { name = "iOS Settings", group = "Settings" }
+//!
+//! [[flows]]
+//! name     = "Onboarding"
+//! category = "iphone-portrait"
+//! screens  = ["welcome/0", "signup", "signup/1", "ipad-portrait:inbox/0"]
+//! ```
+
+use std::collections::{
+    BTreeMap,
+    HashSet,
+};
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use image::imageops::FilterType;
+use regex::Regex;
+use rustc_serialize::Decodable;
+use time;
+use toml;
+
+use theme::Theme;
+use utils;
+
+pub static DEFAULT_PATTERN: &'static str = r"\A[A-Z]{2}-\[(?P<section>[\w-]+)\]-(?P<number>\d+)\.(?i:png|jpe?g|gif|webp|svg)\z";
+
+/// Names exported by Sketch or Figma, eg `Inbox@2x.png`, `inbox_01.jpg`,
+/// `inbox-2.svg`. The scale is not a variant, `Inbox.png` and `Inbox@2x.png`
+/// are the same screen.
+pub static EXPORT_PATTERN: &'static str = r"\A(?P<section>[\w-]+?)(?:[-_](?P<number>\d+))?(?:@\d+x)?\.(?i:png|jpe?g|gif|webp|svg)\z";
+
+/// Eg `iphone-portrait` shown as `iPhone Portrait`.
+#[derive(RustcDecodable, Clone)]
+pub struct CategoryConfig {
+    pub dir:         String,
+    pub name:        String,
+    pub order:       Option<i32>,
+    pub family:      Option<String>,
+    pub orientation: Option<String>,
+    pub thumbnails:  Option<ThumbnailsConfig>,
+}
+
+/// Display name, position and sidebar group of a section, by default its
+/// capitalized class, 0 and none. Also read from the front matter of the
+/// section's notes, see the `notes` module.
+#[derive(RustcDecodable, Clone, Default)]
+pub struct SectionConfig {
+    pub name:  Option<String>,
+    pub order: Option<i32>,
+    pub group: Option<String>,
+}
+
+/// A named path through the screens, eg `Onboarding`. Screens are
+/// `section/number` (or just `section` for its first screen) in the flow's
+/// category, or `category:section/number` in any other one.
+#[derive(RustcDecodable, Clone)]
+pub struct FlowConfig {
+    pub name:     String,
+    pub category: Option<String>,
+    pub screens:  Vec<String>,
+}
+
+/// How the thumbnails are made, set for all categories and optionally
+/// overridden per category. Only one way of sizing may be used: `scale`,
+/// `width`, `height` or `max_width` with `max_height`. The size is for
+/// the 1x thumbnail, other densities are multiples of it. `preview` is the
+/// width of an optional bigger version used on section pages.
+#[derive(RustcDecodable, Clone, Default)]
+pub struct ThumbnailsConfig {
+    pub scale:      Option<f64>,
+    pub width:      Option<u32>,
+    pub height:     Option<u32>,
+    pub max_width:  Option<u32>,
+    pub max_height: Option<u32>,
+    pub filter:     Option<String>,
+    pub densities:  Option<Vec<u32>>,
+    pub preview:    Option<u32>,
+}
+
+/// Thumbnail settings of a category with the defaults filled in.
+/// `fingerprint` changes whenever any of them does.
+pub struct ThumbnailSettings {
+    pub size:        ThumbnailSize,
+    pub filter:      FilterType,
+    pub densities:   Vec<u32>,
+    pub preview:     Option<u32>,
+    pub fingerprint: String,
+}
+
+/// Resolved thumbnail size.
+#[derive(Clone, Copy, Debug)]
+pub enum ThumbnailSize {
+    Scale(f64),
+    Width(u32),
+    Height(u32),
+    Fit(u32, u32),
+}
+
+impl ThumbnailSize {
+    /// Eg the 2x thumbnail is twice the size of the 1x one.
+    pub fn times(self, factor: u32) -> ThumbnailSize {
+        match self {
+            ThumbnailSize::Scale(scale)               => ThumbnailSize::Scale(scale * factor as f64),
+            ThumbnailSize::Width(width)               => ThumbnailSize::Width(width * factor),
+            ThumbnailSize::Height(height)             => ThumbnailSize::Height(height * factor),
+            ThumbnailSize::Fit(max_width, max_height) => ThumbnailSize::Fit(max_width * factor, max_height * factor),
+        }
+    }
+}
+
+/// Limits checked by `mockups lint`.
+pub struct LintConfig {
+    pub max_file_size: u64,
+}
+
+/// Company shown in the page header. The Tapmates wordmark is shown instead
+/// of the name only while the company is the default one.
+pub struct BrandingConfig {
+    pub company:   String,
+    pub copyright: String,
+    pub logo:      Option<PathBuf>,
+    pub favicon:   Option<PathBuf>,
+    pub accent:    Option<String>,
+    pub wordmark:  bool,
+}
+
+/// Everything that can be set in `mockups.toml`, with relative paths
+/// already resolved against the project directory. `force` is not read from
+/// the file, it is only set for a build regenerating everything.
+pub struct Config {
+    pub app_name:       String,
+    pub categories:     Vec<CategoryConfig>,
+    pub pattern:        String,
+    pub thumbnails:     ThumbnailsConfig,
+    pub output:         PathBuf,
+    pub branding:       BrandingConfig,
+    pub templates:      PathBuf,
+    pub theme:          Theme,
+    pub css:            Vec<PathBuf>,
+    pub js:             Vec<PathBuf>,
+    pub sections:       BTreeMap<String, SectionConfig>,
+    pub flows:          Vec<FlowConfig>,
+    pub strict:         bool,
+    pub lint:           LintConfig,
+    pub self_contained: bool,
+    pub force:          bool,
+}
+
+/// Points to the file and, when known, the key that is wrong.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path:    PathBuf,
+    pub key:     Option<String>,
+    pub message: String,
+}
+
+/// The raw file contents, all keys are optional.
+#[derive(RustcDecodable)]
+struct ConfigFile {
+    app_name:       Option<String>,
+    categories:     Option<Vec<CategoryConfig>>,
+    pattern:        Option<String>,
+    thumbnails:     Option<ThumbnailsConfig>,
+    output:         Option<String>,
+    branding:       Option<BrandingFile>,
+    templates:      Option<String>,
+    theme:          Option<String>,
+    css:            Option<Vec<String>>,
+    js:             Option<Vec<String>>,
+    sections:       Option<BTreeMap<String, SectionConfig>>,
+    flows:          Option<Vec<FlowConfig>>,
+    strict:         Option<bool>,
+    lint:           Option<LintFile>,
+    self_contained: Option<bool>,
+}
+
+#[derive(RustcDecodable)]
+struct LintFile {
+    max_file_size: Option<u64>,
+}
+
+#[derive(RustcDecodable)]
+struct BrandingFile {
+    company:   Option<String>,
+    copyright: Option<String>,
+    logo:      Option<String>,
+    favicon:   Option<String>,
+    accent:    Option<String>,
+}
+
+impl CategoryConfig {
+    fn new(dir: &str, name: &str, family: &str, orientation: &str) -> CategoryConfig {
+        CategoryConfig {
+            dir:         dir.to_string(),
+            name:        name.to_string(),
+            order:       None,
+            family:      Some(family.to_string()),
+            orientation: Some(orientation.to_string()),
+            thumbnails:  None,
+        }
+    }
+}
+
+impl SectionConfig {
+    /// Returns what is wrong, for the config file or the notes.
+    pub fn validate(&self) -> Result<(), String> {
+        for &(field, value) in [("name", &self.name), ("group", &self.group)].iter() {
+            if value.as_ref().map_or(false, |value| value.trim().is_empty()) {
+                return Err(format!("{} is empty", field));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FlowConfig {
+    /// Name of the flow's pages, eg `sign-up` for `Sign up` or `přihlášení`
+    /// for `Přihlášení`. Links to them need to be percent-encoded.
+    pub fn slug(&self) -> String {
+        let mut slug = String::new();
+
+        for ch in self.name.chars() {
+            if ch.is_alphanumeric() {
+                slug.extend(ch.to_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with("-") {
+                slug.push('-');
+            }
+        }
+
+        slug.trim_right_matches('-').to_string()
+    }
+
+    /// Category directory and `section/number` of one of the screens.
+    pub fn screen<'a>(&'a self, screen: &'a str) -> (Option<&'a str>, &'a str) {
+        match screen.find(':') {
+            Some(index) => (Some(&screen[..index]), &screen[index + 1..]),
+            None        => (self.category.as_ref().map(|dir| &dir[..]), screen)
+        }
+    }
+}
+
+impl BrandingConfig {
+    /// The copyright with `{year}` and `{company}` filled in, eg
+    /// `All rights reserved. Copyright 2015 by Tapmates`.
+    pub fn copyright_line(&self) -> String {
+        let year = time::now().tm_year + 1900;
+
+        self.copyright
+            .replace("{year}", &year.to_string())
+            .replace("{company}", &self.company)
+    }
+}
+
+impl ThumbnailsConfig {
+    /// `None` when no size is set here.
+    pub fn size(&self) -> Option<ThumbnailSize> {
+        match (self.scale, self.width, self.height, self.max_width, self.max_height) {
+            (Some(scale), _, _, _, _)                    => Some(ThumbnailSize::Scale(scale)),
+            (_, Some(width), _, _, _)                    => Some(ThumbnailSize::Width(width)),
+            (_, _, Some(height), _, _)                   => Some(ThumbnailSize::Height(height)),
+            (_, _, _, Some(max_width), Some(max_height)) => Some(ThumbnailSize::Fit(max_width, max_height)),
+            _                                            => None
+        }
+    }
+
+    fn validate(&self, key: &'static str) -> Result<(), (&'static str, String)> {
+        let sizes = vec![
+            self.scale.is_some(),
+            self.width.is_some(),
+            self.height.is_some(),
+            self.max_width.is_some() || self.max_height.is_some(),
+        ];
+
+        if sizes.into_iter().filter(|set| *set).count() > 1 {
+            return Err((key, "use only one of scale, width, height or max_width with max_height".to_string()));
+        }
+
+        if self.max_width.is_some() != self.max_height.is_some() {
+            return Err((key, "max_width and max_height go together".to_string()));
+        }
+
+        if let Some(scale) = self.scale {
+            if !(scale > 0.0 && scale <= 1.0) {
+                return Err((key, "scale must be greater than 0 and at most 1".to_string()));
+            }
+        }
+
+        for pixels in vec![self.width, self.height, self.max_width, self.max_height, self.preview] {
+            if pixels == Some(0) {
+                return Err((key, "sizes must be greater than 0".to_string()));
+            }
+        }
+
+        if let Some(ref densities) = self.densities {
+            if !densities.contains(&1) || densities.iter().any(|density| *density < 1 || *density > 4) {
+                return Err((key, "densities must contain 1 and be between 1 and 4".to_string()));
+            }
+        }
+
+        if let Some(ref filter) = self.filter {
+            if filter_type(filter).is_none() {
+                return Err((key, format!("unknown filter {:?}, use nearest, triangle, catmullrom, gaussian or lanczos3", filter)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Config {
+    /// The four categories we started with, app name is the name
+    /// of the project directory.
+    pub fn default(project_path: &Path) -> Config {
+        let app_name = match project_path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None       => String::new()
+        };
+
+        Config {
+            app_name:       app_name,
+            categories:     vec![
+                CategoryConfig::new("iphone-portrait",  "iPhone Portrait",  "iphone", "portrait"),
+                CategoryConfig::new("iphone-landscape", "iPhone Landscape", "iphone", "landscape"),
+                CategoryConfig::new("ipad-portrait",    "iPad Portrait",    "ipad",   "portrait"),
+                CategoryConfig::new("ipad-landscape",   "iPad Landscape",   "ipad",   "landscape"),
+            ],
+            pattern:        DEFAULT_PATTERN.to_string(),
+            thumbnails:     ThumbnailsConfig::default(),
+            output:         utils::absolute_path(&project_path.join("site")),
+            branding:       BrandingConfig {
+                company:   "Tapmates".to_string(),
+                copyright: "All rights reserved. Copyright {year} by {company}".to_string(),
+                logo:      None,
+                favicon:   None,
+                accent:    None,
+                wordmark:  true,
+            },
+            templates:      project_path.join("mockups").join("templates"),
+            theme:          Theme::Light,
+            css:            Vec::new(),
+            js:             Vec::new(),
+            sections:       BTreeMap::new(),
+            flows:          Vec::new(),
+            strict:         false,
+            lint:           LintConfig {
+                max_file_size: 5 * 1024,
+            },
+            self_contained: false,
+            force:          false,
+        }
+    }
+
+    /// Read the given config file, or look for `mockups.toml` in the project.
+    /// Without a file the defaults are used.
+    pub fn load(project_path: &Path, config_path: Option<&Path>) -> Result<Config, ConfigError> {
+        let config = Config::default(project_path);
+
+        let config_path = match config_path {
+            Some(path) => path.to_path_buf(),
+            None       => match find_config_file(project_path) {
+                Some(path) => path,
+                None       => return Ok(config)
+            }
+        };
+
+        let mut contents = String::new();
+
+        if let Err(err) = File::open(&config_path).and_then(|mut file| file.read_to_string(&mut contents)) {
+            return Err(ConfigError::new(&config_path, None, format!("cannot be read: {}", err)));
+        }
+
+        let mut parser = toml::Parser::new(&contents);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None        => return Err(ConfigError::new(&config_path, None, utils::toml_errors(&parser, 1)))
+        };
+
+        let mut decoder = toml::Decoder::new(toml::Value::Table(table));
+        let file: ConfigFile = match Decodable::decode(&mut decoder) {
+            Ok(file) => file,
+            Err(err) => return Err(ConfigError::new(&config_path, err.field.clone(), format!("{}", err)))
+        };
+
+        // Whatever the decoder did not consume is a key we do not know.
+        if let Some(toml::Value::Table(ref leftover)) = decoder.toml {
+            if let Some(key) = leftover.keys().next() {
+                return Err(ConfigError::new(&config_path, Some(key.clone()), "unknown key".to_string()));
+            }
+        }
+
+        let config = config.merge(project_path, file);
+
+        match config.validate(project_path) {
+            Ok(())              => Ok(config),
+            Err((key, message)) => Err(ConfigError::new(&config_path, Some(key.to_string()), message))
+        }
+    }
+
+    /// Thumbnail settings of the given category. Category settings win,
+    /// then the global ones, then half the width with the nearest neighbour
+    /// filter in 1x and 2x.
+    pub fn thumbnail_settings(&self, category_dir: &str) -> ThumbnailSettings {
+        let category_thumbnails = self.categories.iter()
+            .find(|category| category.dir == category_dir)
+            .and_then(|category| category.thumbnails.clone())
+            .unwrap_or(ThumbnailsConfig::default());
+
+        let size = category_thumbnails.size()
+            .or(self.thumbnails.size())
+            .unwrap_or(ThumbnailSize::Scale(0.5));
+
+        let filter_name = category_thumbnails.filter.clone()
+            .or(self.thumbnails.filter.clone())
+            .unwrap_or("nearest".to_string());
+
+        let filter = filter_type(&filter_name).unwrap_or(FilterType::Nearest);
+
+        let mut densities = category_thumbnails.densities
+            .or(self.thumbnails.densities.clone())
+            .unwrap_or(vec![1, 2]);
+
+        densities.sort();
+        densities.dedup();
+
+        let preview     = category_thumbnails.preview.or(self.thumbnails.preview);
+        let fingerprint = format!("{:?} {} {:?} {:?}", size, filter_name, densities, preview);
+
+        ThumbnailSettings {
+            size:        size,
+            filter:      filter,
+            densities:   densities,
+            preview:     preview,
+            fingerprint: fingerprint,
+        }
+    }
+
+    fn merge(mut self, project_path: &Path, file: ConfigFile) -> Config {
+        if let Some(app_name) = file.app_name {
+            self.app_name = app_name;
+        }
+
+        if let Some(categories) = file.categories {
+            self.categories = categories;
+        }
+
+        if let Some(pattern) = file.pattern {
+            self.pattern = match &pattern[..] {
+                "default" => DEFAULT_PATTERN.to_string(),
+                "export"  => EXPORT_PATTERN.to_string(),
+                _         => pattern
+            };
+        }
+
+        if let Some(thumbnails) = file.thumbnails {
+            self.thumbnails = thumbnails;
+        }
+
+        if let Some(output) = file.output {
+            self.output = utils::absolute_path(&project_path.join(output));
+        }
+
+        if let Some(branding) = file.branding {
+            if let Some(company) = branding.company {
+                self.branding.wordmark = false;
+                self.branding.company  = company;
+            }
+
+            if let Some(copyright) = branding.copyright {
+                self.branding.copyright = copyright;
+            }
+
+            if let Some(logo) = branding.logo {
+                self.branding.logo = Some(project_path.join(logo));
+            }
+
+            if let Some(favicon) = branding.favicon {
+                self.branding.favicon = Some(project_path.join(favicon));
+            }
+
+            if branding.accent.is_some() {
+                self.branding.accent = branding.accent;
+            }
+        }
+
+        if let Some(templates) = file.templates {
+            self.templates = project_path.join(templates);
+        }
+
+        if let Some(theme) = file.theme {
+            self.theme = Theme::parse(project_path, &theme);
+        }
+
+        if let Some(css) = file.css {
+            self.css = css.iter().map(|file| project_path.join(file)).collect();
+        }
+
+        if let Some(js) = file.js {
+            self.js = js.iter().map(|file| project_path.join(file)).collect();
+        }
+
+        if let Some(sections) = file.sections {
+            self.sections = sections;
+        }
+
+        if let Some(flows) = file.flows {
+            self.flows = flows;
+        }
+
+        if let Some(strict) = file.strict {
+            self.strict = strict;
+        }
+
+        if let Some(self_contained) = file.self_contained {
+            self.self_contained = self_contained;
+        }
+
+        if let Some(lint) = file.lint {
+            if let Some(max_file_size) = lint.max_file_size {
+                self.lint.max_file_size = max_file_size;
+            }
+        }
+
+        self
+    }
+
+    /// Returns the offending key and what is wrong with it.
+    fn validate(&self, project_path: &Path) -> Result<(), (&'static str, String)> {
+        if self.categories.is_empty() {
+            return Err(("categories", "at least one category is needed".to_string()));
+        }
+
+        let mut dirs = HashSet::new();
+
+        for category in self.categories.iter() {
+            if category.dir.is_empty() || category.dir.contains('/') || category.dir.contains('\\') || category.dir == "mockups" {
+                return Err(("categories.dir", format!("{:?} is not a valid directory name", category.dir)));
+            }
+
+            if !dirs.insert(category.dir.clone()) {
+                return Err(("categories.dir", format!("{:?} is listed more than once", category.dir)));
+            }
+
+            match category.orientation {
+                None => (),
+                Some(ref orientation) if orientation == "portrait" || orientation == "landscape" => (),
+                Some(ref orientation) => {
+                    return Err(("categories.orientation", format!("{:?} is neither \"portrait\" nor \"landscape\"", orientation)));
+                }
+            }
+        }
+
+        let regex = match Regex::new(&self.pattern) {
+            Ok(regex) => regex,
+            Err(err)  => return Err(("pattern", format!("{}", err)))
+        };
+
+        if !regex.capture_names().any(|capture| capture == Some("section")) {
+            return Err(("pattern", "a named capture (?P<section>...) is missing".to_string()));
+        }
+
+        for name in regex.capture_names().filter_map(|capture| capture) {
+            match name {
+                "section" | "number" | "state" | "variant" | "version" => (),
+                _ => return Err(("pattern", format!("unknown named capture (?P<{}>...), use section, number, state, variant or version", name)))
+            }
+        }
+
+        try!(self.thumbnails.validate("thumbnails"));
+
+        for category in self.categories.iter() {
+            if let Some(ref thumbnails) = category.thumbnails {
+                try!(thumbnails.validate("categories.thumbnails"));
+            }
+        }
+
+        if let Err(message) = self.check_output(project_path) {
+            return Err(("output", message));
+        }
+
+        if let Some(ref accent) = self.branding.accent {
+            if !is_colour(accent) {
+                return Err(("branding.accent", format!("{:?} is not a colour like \"#961fd6\"", accent)));
+            }
+        }
+
+        for &(key, path) in [("branding.logo", &self.branding.logo), ("branding.favicon", &self.branding.favicon)].iter() {
+            if let Some(ref path) = *path {
+                if !utils::is_file(path) {
+                    return Err((key, format!("{} does not exist", path.display())));
+                }
+            }
+        }
+
+        if let Theme::Dir(ref theme_path) = self.theme {
+            if !utils::is_dir(theme_path) {
+                return Err(("theme", format!("{} is neither light, dark, presentation nor a directory", theme_path.display())));
+            }
+        }
+
+        for &(key, files) in [("css", &self.css), ("js", &self.js)].iter() {
+            if let Some(path) = files.iter().find(|path| !utils::is_file(path)) {
+                return Err((key, format!("{} does not exist", path.display())));
+            }
+        }
+
+        for (class, section) in self.sections.iter() {
+            if let Err(message) = section.validate() {
+                return Err(("sections", format!("{:?}: {}", class, message)));
+            }
+        }
+
+        try!(self.validate_flows(&dirs));
+
+        Ok(())
+    }
+
+    /// Whether the screens of the flows can be found, as far as that is
+    /// known without reading the category directories.
+    fn validate_flows(&self, dirs: &HashSet<String>) -> Result<(), (&'static str, String)> {
+        if !self.flows.is_empty() && dirs.contains("flows") {
+            return Err(("categories.dir", "\"flows\" is used for the flow pages when there are flows".to_string()));
+        }
+
+        let mut slugs = HashSet::new();
+
+        for flow in self.flows.iter() {
+            let slug = flow.slug();
+
+            if slug.is_empty() {
+                return Err(("flows.name", format!("{:?} needs at least one letter or digit", flow.name)));
+            }
+
+            if !slugs.insert(slug) {
+                return Err(("flows.name", format!("{:?} is used by another flow", flow.name)));
+            }
+
+            if flow.screens.is_empty() {
+                return Err(("flows.screens", format!("{:?} has no screens", flow.name)));
+            }
+
+            for screen in flow.screens.iter() {
+                match flow.screen(screen) {
+                    (Some(dir), _) if dirs.contains(dir) => (),
+                    (Some(dir), _) => {
+                        return Err(("flows.screens", format!("{:?} of {:?} is not a category", dir, flow.name)));
+                    },
+                    (None, _) => {
+                        return Err(("flows.screens", format!("{:?} of {:?} has no category, set one for the flow or write eg \"iphone-portrait:{}\"", screen, flow.name, screen)));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Put the site into `output`, relative to the current directory, eg for
+    /// `--out`. Returns what is wrong with it when it cannot be used.
+    pub fn set_output(&mut self, project_path: &Path, output: &Path) -> Result<(), String> {
+        self.output = utils::absolute_path(output);
+        self.check_output(project_path)
+    }
+
+    /// The output directory may be anywhere, as long as neither it nor the
+    /// category pages in it end up in the category directories or `mockups`.
+    fn check_output(&self, project_path: &Path) -> Result<(), String> {
+        let project_path = utils::absolute_path(project_path);
+        let output_path  = utils::absolute_path(&self.output);
+
+        let mut sources = self.categories.iter()
+            .map(|category| project_path.join(&category.dir))
+            .collect::<Vec<PathBuf>>();
+        sources.push(project_path.join("mockups"));
+
+        let pages = self.categories.iter()
+            .map(|category| output_path.join(&category.dir))
+            .collect::<Vec<PathBuf>>();
+
+        for source in sources.iter() {
+            if output_path.starts_with(source) || pages.iter().any(|page_path| source.starts_with(page_path)) {
+                return Err(format!("{} would write into the source directory {}", output_path.display(), source.display()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ConfigError {
+    fn new(path: &Path, key: Option<String>, message: String) -> ConfigError {
+        ConfigError {
+            path:    path.to_path_buf(),
+            key:     key,
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.key {
+            Some(ref key) => write!(f, "{}: `{}`: {}", self.path.display(), key, self.message),
+            None          => write!(f, "{}: {}", self.path.display(), self.message)
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn description(&self) -> &str {
+        "invalid config file"
+    }
+}
+
+fn filter_type(name: &str) -> Option<FilterType> {
+    match name {
+        "nearest"    => Some(FilterType::Nearest),
+        "triangle"   => Some(FilterType::Triangle),
+        "catmullrom" => Some(FilterType::CatmullRom),
+        "gaussian"   => Some(FilterType::Gaussian),
+        "lanczos3"   => Some(FilterType::Lanczos3),
+        _            => None
+    }
+}
+
+/// `Project/mockups.toml` or `Project/mockups/mockups.toml`.
+fn find_config_file(project_path: &Path) -> Option<PathBuf> {
+    let candidates = vec![
+        project_path.join("mockups.toml"),
+        project_path.join("mockups").join("mockups.toml"),
+    ];
+
+    candidates.into_iter().find(|path| utils::is_file(path))
+}
+
+/// `#rgb` or `#rrggbb`.
+fn is_colour(colour: &str) -> bool {
+    (colour.len() == 4 || colour.len() == 7)
+        && colour.starts_with("#")
+        && colour[1..].chars().all(|ch| ch.is_digit(16))
+}
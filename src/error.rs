@@ -0,0 +1,140 @@
+//! Errors of a build. A broken image or an unwritable page does not stop
+//! the build, errors are collected and reported at the end instead.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+
+use image::ImageError;
+
+use config::ConfigError;
+
+#[derive(Debug)]
+pub enum Error {
+    Config(ConfigError),
+    NotADirectory(PathBuf),
+    Pattern(String),
+    CreateDir(PathBuf, io::Error),
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+    Copy(PathBuf, PathBuf, io::Error),
+    Decode(PathBuf, ImageError),
+    Encode(PathBuf, ImageError),
+    /// File (or `embedded site.mustache`), line and what is wrong.
+    Template(String, usize, String),
+    /// Sidecar or `links.toml` and what is wrong with it.
+    Hotspots(PathBuf, String),
+    /// Notes file and what is wrong with its front matter.
+    Notes(PathBuf, String),
+    /// Name of the flow and the screen which is not there.
+    Flow(String, String),
+    /// A thumbnail worker died without telling what went wrong.
+    Crashed(PathBuf),
+    /// Everything that failed in one step, eg rendering the site. The rest
+    /// of the step was done nevertheless.
+    Build(Vec<Error>),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Error {
+        Error::Config(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Config(ref err)                         => write!(f, "{}", err),
+            Error::NotADirectory(ref path)                 => write!(f, "{} is not a directory", path.display()),
+            Error::Pattern(ref message)                    => write!(f, "Invalid filename pattern: {}", message),
+            Error::CreateDir(ref path, ref err)            => write!(f, "Cannot create directory {}: {}", path.display(), err),
+            Error::Read(ref path, ref err)                 => write!(f, "Cannot read {}: {}", path.display(), err),
+            Error::Write(ref path, ref err)                => write!(f, "Cannot write {}: {}", path.display(), err),
+            Error::Copy(ref from, ref to, ref err)         => write!(f, "Cannot copy {} to {}: {}", from.display(), to.display(), err),
+            Error::Decode(ref path, ref err)               => write!(f, "Cannot decode image {}: {}", path.display(), err),
+            Error::Encode(ref path, ref err)               => write!(f, "Cannot save image {}: {}", path.display(), err),
+            Error::Template(ref origin, line, ref message) => write!(f, "{}:{}: {}", origin, line, message),
+            Error::Hotspots(ref path, ref message)         => write!(f, "{}: {}", path.display(), message),
+            Error::Notes(ref path, ref message)            => write!(f, "{}: {}", path.display(), message),
+            Error::Flow(ref name, ref message)             => write!(f, "Flow {:?}: {}", name, message),
+            Error::Crashed(ref path)                       => write!(f, "Crashed while generating {}", path.display()),
+            Error::Build(ref errors)                       => {
+                let messages = errors.iter().map(|err| format!("{}", err)).collect::<Vec<String>>();
+                write!(f, "{}", messages.connect("\n"))
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Config(_)         => "invalid config file",
+            Error::NotADirectory(_)  => "not a directory",
+            Error::Pattern(_)        => "invalid filename pattern",
+            Error::CreateDir(_, _)   => "cannot create directory",
+            Error::Read(_, _)        => "cannot read file",
+            Error::Write(_, _)       => "cannot write file",
+            Error::Copy(_, _, _)     => "cannot copy file",
+            Error::Decode(_, _)      => "cannot decode image",
+            Error::Encode(_, _)      => "cannot save image",
+            Error::Template(_, _, _) => "invalid template",
+            Error::Hotspots(_, _)    => "invalid hotspots",
+            Error::Notes(_, _)       => "invalid notes front matter",
+            Error::Flow(_, _)        => "invalid flow",
+            Error::Crashed(_)        => "thumbnail worker crashed",
+            Error::Build(_)          => "build failed",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Config(ref err)       => Some(err),
+            Error::CreateDir(_, ref err) => Some(err),
+            Error::Read(_, ref err)      => Some(err),
+            Error::Write(_, ref err)     => Some(err),
+            Error::Copy(_, _, ref err)   => Some(err),
+            _                            => None
+        }
+    }
+}
+
+impl Error {
+    /// `Err(Error::Build(errors))` unless there are none.
+    pub fn from_errors(errors: Vec<Error>) -> Result<()> {
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Build(errors))
+        }
+    }
+}
+
+/// Print all errors of the build, nothing when there are none.
+pub fn report(errors: &Vec<Error>) {
+    let mut all = Vec::new();
+    flatten(errors, &mut all);
+
+    if all.is_empty() {
+        return;
+    }
+
+    println!("{} error(s):", all.len());
+
+    for err in all {
+        println!("  {}", err);
+    }
+}
+
+fn flatten<'a>(errors: &'a Vec<Error>, all: &mut Vec<&'a Error>) {
+    for err in errors.iter() {
+        match *err {
+            Error::Build(ref errors) => flatten(errors, all),
+            _                        => all.push(err)
+        }
+    }
+}
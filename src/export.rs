@@ -0,0 +1,138 @@
+//! The scanned project as JSON, see `mockups export`. Paths are relative to
+//! the project directory, URLs to the output directory.
+
+use rustc_serialize::json;
+
+use config::Config;
+use images;
+use site;
+
+use structure::{
+    Category,
+    Image,
+    Section,
+};
+
+use Project;
+
+#[derive(RustcEncodable)]
+struct ProjectExport {
+    app_name:   String,
+    categories: Vec<CategoryExport>,
+}
+
+#[derive(RustcEncodable)]
+struct CategoryExport {
+    dir:         String,
+    name:        String,
+    family:      String,
+    orientation: Option<String>,
+    url:         String,
+    screens:     usize,
+    sections:    Vec<SectionExport>,
+}
+
+#[derive(RustcEncodable)]
+struct SectionExport {
+    name:    String,
+    class:   String,
+    url:     String,
+    screens: usize,
+    images:  Vec<ImageExport>,
+}
+
+#[derive(RustcEncodable)]
+struct ImageExport {
+    file:        String,
+    path:        String,
+    number:      u32,
+    label:       String,
+    state:       Option<String>,
+    variant:     Option<String>,
+    version:     Option<String>,
+    width:       Option<u32>,
+    height:      Option<u32>,
+    url:         String,
+    thumbnails:  Vec<ThumbnailExport>,
+    preview_url: Option<String>,
+}
+
+/// `width` is missing when the dimensions of the original are not known,
+/// eg for SVG without a size or `viewBox`.
+#[derive(RustcEncodable)]
+struct ThumbnailExport {
+    density: u32,
+    width:   Option<u32>,
+    url:     String,
+}
+
+/// Pretty-printed `{"app_name": ..., "categories": [...]}`.
+pub fn to_json(project: &Project, config: &Config) -> String {
+    let project_url = site::project_url(&project.path, &config.output);
+
+    let export = ProjectExport {
+        app_name:   config.app_name.clone(),
+        categories: project.categories.iter()
+            .map(|category| export_category(config, &project_url, category))
+            .collect(),
+    };
+
+    format!("{}", json::as_pretty_json(&export))
+}
+
+fn export_category(config: &Config, project_url: &str, category: &Category) -> CategoryExport {
+    let sections = category.sections.iter()
+        .map(|section| export_section(config, project_url, category, section))
+        .collect::<Vec<SectionExport>>();
+
+    CategoryExport {
+        dir:         category.file.clone(),
+        name:        category.name.clone(),
+        family:      category.family.clone(),
+        orientation: category.orientation.clone(),
+        url:         format!("{}/index.html", category.file),
+        screens:     sections.iter().map(|section| section.screens).fold(0, |sum, screens| sum + screens),
+        sections:    sections,
+    }
+}
+
+fn export_section(config: &Config, project_url: &str, category: &Category, section: &Section) -> SectionExport {
+    SectionExport {
+        name:    section.name.clone(),
+        class:   section.class.clone(),
+        url:     format!("{}/{}", category.file, section.file),
+        screens: section.images.len(),
+        images:  section.images.iter()
+            .map(|image| export_image(config, project_url, image))
+            .collect(),
+    }
+}
+
+fn export_image(config: &Config, project_url: &str, image: &Image) -> ImageExport {
+    let settings = config.thumbnail_settings(&image.category);
+    let variants = images::variants(&settings, image);
+
+    ImageExport {
+        file:        image.file.clone(),
+        path:        format!("{}/{}", image.category, image.file),
+        number:      image.number,
+        label:       image.label(),
+        state:       image.state.clone(),
+        variant:     image.variant.clone(),
+        version:     image.version.clone(),
+        width:       image.width,
+        height:      image.height,
+        url:         site::original_url(config, project_url, image),
+        thumbnails:  variants.iter()
+            .filter(|variant| variant.dir == "thumbs")
+            .map(|variant| ThumbnailExport {
+                density: variant.density,
+                width:   variant.width,
+                url:     variant.url.clone(),
+            })
+            .collect(),
+        preview_url: variants.iter()
+            .find(|variant| variant.dir == "previews")
+            .map(|variant| variant.url.clone()),
+    }
+}
@@ -0,0 +1,67 @@
+//! Flows are named paths through the screens, eg `Onboarding`, possibly
+//! across categories. They are set in `mockups.toml` (see the `config`
+//! module) and every flow gets a storyboard page, `site/flows/onboarding.html`,
+//! and a presenter going through the screens one by one,
+//! `site/flows/onboarding.present.html`.
+
+use config::Config;
+use error::Error;
+use structure::{
+    Category,
+    Image,
+    Section,
+};
+
+/// A flow with its screens found in the categories.
+pub struct Flow<'a> {
+    pub name:  String,
+    pub slug:  String,
+    pub steps: Vec<Step<'a>>,
+}
+
+/// One screen of a flow.
+pub struct Step<'a> {
+    pub category: &'a Category,
+    pub section:  &'a Section,
+    pub image:    &'a Image,
+}
+
+/// Find the screens of all flows. Screens which are not there are left out
+/// and collected in `errors`.
+pub fn resolve<'a>(config: &Config, categories: &'a Vec<Category>, errors: &mut Vec<Error>) -> Vec<Flow<'a>> {
+    let mut flows = Vec::new();
+
+    for flow in config.flows.iter() {
+        let mut steps = Vec::new();
+
+        for screen in flow.screens.iter() {
+            let (dir, reference) = flow.screen(screen);
+            let dir              = dir.unwrap_or("");
+
+            let found = categories.iter()
+                .find(|category| category.file == dir)
+                .and_then(|category| {
+                    category.find_screen(reference).map(|(section, image)| (category, section, image))
+                });
+
+            match found {
+                Some((category, section, image)) => {
+                    steps.push(Step {
+                        category: category,
+                        section:  section,
+                        image:    image,
+                    });
+                },
+                None => errors.push(Error::Flow(flow.name.clone(), format!("`{}` is not a screen in {}", reference, dir)))
+            }
+        }
+
+        flows.push(Flow {
+            name:  flow.name.clone(),
+            slug:  flow.slug(),
+            steps: steps,
+        });
+    }
+
+    flows
+}
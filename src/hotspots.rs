@@ -0,0 +1,203 @@
+//! Hotspots link areas of a screen to other screens of the same category,
+//! for the clickable prototype pages. They are read from a sidecar file
+//! next to the image, eg `XY-[inbox]-0.hotspots.json`:
+//!
+//! ```
+//! [
+//!   {"x": 0, "y": 88, "width": 375, "height": 72, "to": "detail/0"},
+//!   {"x": 16, "y": 20, "width": 44, "height": 44, "to": "menu"}
+//! ]
+//! ```
+//!
+//! or for the whole project from `mockups/links.toml`:
+//!
+//! ```
+//! [[links]]
+//! category = "iphone-portrait" # Optional, all categories by default
+//! screen   = "inbox/0"
+//! x        = 0
+//! y        = 88
+//! width    = 375
+//! height   = 72
+//! to       = "detail/0"
+//! ```
+//!
+//! Coordinates are pixels of the original image. Screens are referred to as
+//! `section/number`, or just `section` for its first screen.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rustc_serialize::Decodable;
+use rustc_serialize::json;
+use toml;
+
+use error::{
+    Error,
+    Result,
+};
+use structure::Category;
+use utils;
+
+/// A clickable area, `target` is the file of the image it leads to.
+pub struct Hotspot {
+    pub x:      u32,
+    pub y:      u32,
+    pub width:  u32,
+    pub height: u32,
+    pub to:     String,
+    pub target: String,
+}
+
+/// Hotspots by category directory and image file.
+pub type Hotspots = BTreeMap<(String, String), Vec<Hotspot>>;
+
+#[derive(RustcDecodable)]
+struct HotspotFile {
+    x:      u32,
+    y:      u32,
+    width:  u32,
+    height: u32,
+    to:     String,
+}
+
+#[derive(RustcDecodable)]
+struct LinksFile {
+    links: Vec<LinkFile>,
+}
+
+#[derive(RustcDecodable)]
+struct LinkFile {
+    category: Option<String>,
+    screen:   String,
+    x:        u32,
+    y:        u32,
+    width:    u32,
+    height:   u32,
+    to:       String,
+}
+
+/// Read the sidecar files and `mockups/links.toml`. Files which cannot be
+/// read and links to screens which do not exist are collected in `errors`.
+pub fn load(project_path: &Path, categories: &Vec<Category>, errors: &mut Vec<Error>) -> Hotspots {
+    let mut hotspots = Hotspots::new();
+
+    for category in categories.iter() {
+        for section in category.sections.iter() {
+            for image in section.images.iter() {
+                let sidecar_path = project_path.join(&category.file).join(sidecar_file(&image.file));
+
+                if !utils::is_file(&sidecar_path) {
+                    continue;
+                }
+
+                let areas = match read_sidecar(&sidecar_path) {
+                    Ok(areas) => areas,
+                    Err(err)  => {
+                        errors.push(err);
+                        continue;
+                    }
+                };
+
+                for area in areas {
+                    add(&mut hotspots, &sidecar_path, category, &image.file, area, errors);
+                }
+            }
+        }
+    }
+
+    let links_path = project_path.join("mockups").join("links.toml");
+
+    if utils::is_file(&links_path) {
+        match read_links(&links_path) {
+            Ok(links) => {
+                for link in links {
+                    let mut taken = false;
+
+                    for category in categories.iter().filter(|category| link.category.as_ref().map_or(true, |dir| *dir == category.file)) {
+                        // Links for all categories only apply where the screen is.
+                        let file = match category.find_screen(&link.screen) {
+                            Some((_, image)) => image.file.clone(),
+                            None             => {
+                                if link.category.is_some() {
+                                    errors.push(Error::Hotspots(links_path.clone(), format!("`{}` is not a screen in {}", link.screen, category.file)));
+                                }
+
+                                continue;
+                            }
+                        };
+
+                        let area = HotspotFile {
+                            x:      link.x,
+                            y:      link.y,
+                            width:  link.width,
+                            height: link.height,
+                            to:     link.to.clone(),
+                        };
+
+                        add(&mut hotspots, &links_path, category, &file, area, errors);
+                        taken = true;
+                    }
+
+                    // Eg a typo in the screen, it would be lost without a word.
+                    if !taken && link.category.is_none() {
+                        errors.push(Error::Hotspots(links_path.clone(), format!("`{}` is not a screen in any category", link.screen)));
+                    }
+                }
+            },
+            Err(err) => errors.push(err)
+        }
+    }
+
+    hotspots
+}
+
+/// `XY-[inbox]-0.png -> XY-[inbox]-0.hotspots.json`
+pub fn sidecar_file(image_file: &str) -> String {
+    format!("{}.hotspots.json", utils::file_stem(image_file))
+}
+
+/// `source_path` is where the area comes from, for errors.
+fn add(hotspots: &mut Hotspots, source_path: &Path, category: &Category, file: &str, area: HotspotFile, errors: &mut Vec<Error>) {
+    let target = match category.find_screen(&area.to) {
+        Some((_, image)) => image.file.clone(),
+        None             => {
+            errors.push(Error::Hotspots(source_path.to_path_buf(), format!("`{}` is not a screen in {}", area.to, category.file)));
+            return;
+        }
+    };
+
+    let hotspot = Hotspot {
+        x:      area.x,
+        y:      area.y,
+        width:  area.width,
+        height: area.height,
+        to:     area.to,
+        target: target,
+    };
+
+    hotspots.entry((category.file.clone(), file.to_string())).or_insert(Vec::new()).push(hotspot);
+}
+
+fn read_sidecar(sidecar_path: &Path) -> Result<Vec<HotspotFile>> {
+    let contents = try!(utils::read_file(sidecar_path));
+
+    json::decode(&contents).map_err(|err| Error::Hotspots(sidecar_path.to_path_buf(), format!("{}", err)))
+}
+
+fn read_links(links_path: &Path) -> Result<Vec<LinkFile>> {
+    let contents   = try!(utils::read_file(links_path));
+    let mut parser = toml::Parser::new(&contents);
+
+    let table = match parser.parse() {
+        Some(table) => table,
+        None        => return Err(Error::Hotspots(links_path.to_path_buf(), utils::toml_errors(&parser, 1)))
+    };
+
+    let mut decoder = toml::Decoder::new(toml::Value::Table(table));
+
+    let file: LinksFile = try!(Decodable::decode(&mut decoder)
+        .map_err(|err| Error::Hotspots(links_path.to_path_buf(), format!("{}", err))));
+
+    Ok(file.links)
+}
@@ -1,47 +1,102 @@
-//! Copy images and resize them by half.
+//! Copy images and resize them, by half unless configured otherwise, see
+//! `config::ThumbnailsConfig`. Every image gets a thumbnail per pixel
+//! density and optionally a bigger preview.
+//! PNG and JPEG thumbnails keep their format, GIF and WebP become PNG and
+//! SVG is copied as it is, browsers scale it just fine.
 
+use std::collections::BTreeMap;
+use std::fs;
 use std::fs::File;
 use std::path::Path;
 use threadpool::ThreadPool;
 use std::sync::mpsc::channel;
-use std::io::Write;
-use std::io;
+use std::io::Read;
 use sys_info;
 
 use image;
 
-use image::{
-    GenericImage,
-    imageops,
+use image::GenericImage;
+use image::imageops::FilterType;
+
+use url::percent_encoding::{
+    FORM_URLENCODED_ENCODE_SET,
+    utf8_percent_encode,
+};
+
+use config::{
+    Config,
+    ThumbnailSettings,
+    ThumbnailSize,
+};
+
+use error::{
+    Error,
+    Result,
+};
+
+use manifest::{
+    ImageEntry,
+    Manifest,
+};
+
+use structure::{
+    Category,
+    Image,
 };
 
-use structure::Category;
 use utils;
+use Stats;
+
+/// One generated file of a mockup, eg the 2x thumbnail or the preview.
+/// Paths and URLs are relative to the output directory.
+pub struct Variant {
+    pub dir:     &'static str,
+    pub file:    String,
+    pub url:     String,
+    pub density: u32,
+    pub width:   Option<u32>,
+    pub size:    ThumbnailSize,
+}
 
 /// Generate smaller versions of mockup images.
 /// `iphone-portrait/XY-[section-a]-0.png -> site/thumbs/iphone-portrait/XY-[section-a]-0.png`
-pub fn generate_thumbs(project_path: &Path, categories: &Vec<Category>) {
-    let thumbs_path = project_path.join("site").join("thumbs");
-
-    // Create thumbs directory
-    utils::create_dir(&thumbs_path);
-
+/// `iphone-portrait/XY-[section-a]-0.png -> site/thumbs/iphone-portrait/XY-[section-a]-0@2x.png`
+/// `iphone-portrait/XY-[section-a]-0.png -> site/previews/iphone-portrait/XY-[section-a]-0.png`
+///
+/// Images whose source and settings did not change since the previous build
+/// are skipped, variants of removed images are deleted. The manifest is
+/// updated with what is on disk now. Images which cannot be read, decoded
+/// or saved are collected in `errors` and tried again in the next build.
+pub fn generate_thumbs(project_path: &Path, config: &Config, categories: &Vec<Category>, manifest: &mut Manifest, errors: &mut Vec<Error>) -> Stats {
     // Task pool so we don't overwhelm the system with hundreds of threads.
     // Use as many threads as there are CPU cores + the main thread.
-    let num_cpus = sys_info::cpu_num().ok().expect("Cannot deternimne number of cores") as usize;
+    let num_cpus = sys_info::cpu_num().unwrap_or(1) as usize;
     let pool     = ThreadPool::new(num_cpus);
 
-    // We need channels so we can wait until the tasks are done.
-    let (tx, rx) = channel();
-    let mut total = 0u8;
+    // We need channels so we can wait until the tasks are done. Every task
+    // reports its index in `tasks`, the image key and target of the task.
+    let (tx, rx)  = channel();
+    let mut tasks = Vec::new();
+
+    let mut entries   = BTreeMap::new();
+    let mut unchanged = 0usize;
 
     // Loop over the categories/sections/images and spawn a new task
-    // for thumb generation.
+    // for every variant of every image.
     for category in categories.iter() {
-        let category_path = thumbs_path.join(category.file.clone());
+        let settings = config.thumbnail_settings(&category.file);
+        let filter   = settings.filter;
 
-        // The site/thumbs/iphone-portrait directory
-        utils::create_dir(&category_path);
+        // The site/thumbs/iphone-portrait and site/previews/iphone-portrait directories
+        for dir in vec!["thumbs", "previews"] {
+            if dir == "previews" && settings.preview.is_none() {
+                continue;
+            }
+
+            if let Err(err) = utils::create_dir(&config.output.join(dir).join(category.file.clone())) {
+                errors.push(err);
+            }
+        }
 
         for section in category.sections.iter() {
             for image in section.images.iter() {
@@ -49,53 +104,511 @@ pub fn generate_thumbs(project_path: &Path, categories: &Vec<Category>) {
                     .join(image.category.clone())
                     .join(image.file.clone());
 
-                if !utils::is_file(&source_image_path) {
-                    println!("{:?} does not exist!", source_image_path);
+                let image_variants = variants(&settings, image);
+                let variant_files  = image_variants.iter().map(|variant| variant.file.clone()).collect::<Vec<String>>();
+                let key            = format!("{}/{}", image.category, image.file);
+                let previous       = manifest.images.get(&key);
+
+                let entry = match ImageEntry::read(&source_image_path, &settings.fingerprint, variant_files, previous) {
+                    Ok(entry) => entry,
+                    Err(err)  => {
+                        errors.push(err);
+                        continue;
+                    }
+                };
+
+                let fresh = match previous {
+                    Some(previous) => !config.force && entry.is_fresh(previous, &config.output),
+                    None           => false
+                };
+
+                entries.insert(key.clone(), entry);
+
+                if fresh {
+                    unchanged += 1;
                     continue;
                 }
 
-                let target_image_path = thumbs_path
-                    .join(image.category.clone())
-                    .join(image.file.clone());
+                for variant in image_variants {
+                    let source_image_path = source_image_path.clone();
+                    let target_image_path = config.output.join(&variant.file);
+                    let size              = variant.size;
 
-                total += 1;
-                let tx = tx.clone();
+                    let index = tasks.len();
+                    tasks.push((key.clone(), target_image_path.clone()));
+                    let tx = tx.clone();
 
-                pool.execute(move || {
-                    resize_image(&source_image_path, &target_image_path);
-                    let _ = tx.send(());
-                });
+                    pool.execute(move || {
+                        let result = resize_image(&source_image_path, &target_image_path, size, filter);
+                        let _ = tx.send((index, result));
+                    });
+                }
             }
         }
     }
 
-    for _ in 0u8..total {
-        let _ = rx.recv();
+    // Only the tasks hold a sender now. A task which panics drops its sender
+    // without reporting, so the loop ends even then instead of waiting forever.
+    drop(tx);
+
+    let mut done      = vec![false; tasks.len()];
+    let mut failed    = Vec::new();
+    let mut generated = 0usize;
+
+    for (index, result) in rx.iter() {
+        done[index] = true;
+
+        match result {
+            Ok(())   => generated += 1,
+            Err(err) => {
+                errors.push(err);
+                failed.push(tasks[index].0.clone());
+            }
+        }
+    }
+
+    for (index, &(ref key, ref target_image_path)) in tasks.iter().enumerate() {
+        if !done[index] {
+            errors.push(Error::Crashed(target_image_path.clone()));
+            failed.push(key.clone());
+        }
     }
 
-    print!("\n");
+    // Not remembering failed images makes the next build try them again.
+    for key in failed.iter() {
+        entries.remove(key);
+    }
+
+    // Variants which are not produced any more, either because the source
+    // is gone or because the settings changed.
+    let mut removed = 0usize;
+
+    for (key, previous) in manifest.images.iter() {
+        for variant in previous.variants.iter() {
+            let still_used = entries.get(key).map_or(false, |entry: &ImageEntry| entry.variants.contains(variant));
+
+            if !still_used && fs::remove_file(config.output.join(variant)).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+
+    manifest.images = entries;
+
+    Stats {
+        written:   generated,
+        unchanged: unchanged,
+        removed:   removed,
+    }
 }
 
-fn resize_image(source_image_path: &Path, target_image_path: &Path) {
-    let img = image::open(source_image_path)
-        .ok()
-        .expect(&format!("Image {:?} does not exist!", source_image_path));
+/// Thumbnails in every density, smallest first, followed by the preview.
+/// SVG images only get the 1x thumbnail, they are sharp anyway. Thumbnails
+/// are never bigger than the original, so a density which comes out the
+/// same size as a smaller one is left out.
+pub fn variants(settings: &ThumbnailSettings, image: &Image) -> Vec<Variant> {
+    let is_svg       = extension(Path::new(&image.file)) == "svg";
+    let mut variants = Vec::new();
 
-    let (width, height) = img.dimensions();
-    let nwidth          = width / 2;
-    let ratio           = nwidth as f64 / width as f64;
-    let nheight         = (height as f64 * ratio).round() as u32;
-    let resized_img     = img.resize(nwidth, nheight, imageops::Nearest);
+    for density in settings.densities.iter() {
+        if is_svg && *density != 1 {
+            continue;
+        }
+
+        let file = if *density == 1 {
+            image.thumb_file.clone()
+        } else {
+            density_file(&image.thumb_file, *density)
+        };
+
+        let variant = Variant::new("thumbs", image, &file, *density, settings.size.times(*density));
+
+        if variant.width.is_some() && variants.iter().any(|other: &Variant| other.width == variant.width) {
+            continue;
+        }
+
+        variants.push(variant);
+    }
+
+    if let Some(preview) = settings.preview {
+        if !is_svg {
+            variants.push(Variant::new("previews", image, &image.thumb_file, 0, ThumbnailSize::Width(preview)));
+        }
+    }
+
+    variants
+}
+
+impl Variant {
+    fn new(dir: &'static str, image: &Image, file: &str, density: u32, size: ThumbnailSize) -> Variant {
+        let width = match (image.width, image.height) {
+            (Some(width), Some(height)) => Some(thumb_dimensions(width, height, size).0),
+            _                           => None
+        };
+
+        Variant {
+            dir:     dir,
+            file:    format!("{}/{}/{}", dir, image.category, file),
+            url:     format!("{}/{}/{}", dir, image.category, utf8_percent_encode(file, FORM_URLENCODED_ENCODE_SET)),
+            density: density,
+            width:   width,
+            size:    size,
+        }
+    }
+}
+
+/// `XY-[inbox]-0.png -> XY-[inbox]-0@2x.png`
+fn density_file(file: &str, density: u32) -> String {
+    match file.rfind('.') {
+        Some(index) => format!("{}@{}x{}", &file[..index], density, &file[index..]),
+        None        => format!("{}@{}x", file, density)
+    }
+}
+
+/// `XY-[inbox]-0.png -> XY-[inbox]-0.png`, `XY-[inbox]-0.webp -> XY-[inbox]-0.webp.png`.
+pub fn thumb_file(file: &str) -> String {
+    match &extension(Path::new(file))[..] {
+        "png" | "jpg" | "jpeg" | "svg" => file.to_string(),
+        _                              => format!("{}.png", file)
+    }
+}
+
+/// Lowercase extension, eg `jpg`, empty when there is none.
+pub fn extension(image_path: &Path) -> String {
+    image_path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .unwrap_or(String::new())
+}
+
+fn resize_image(source_image_path: &Path, target_image_path: &Path, size: ThumbnailSize, filter: FilterType) -> Result<()> {
+    if extension(source_image_path) == "svg" {
+        try!(fs::copy(source_image_path, target_image_path)
+            .map_err(|err| Error::Copy(source_image_path.to_path_buf(), target_image_path.to_path_buf(), err)));
+
+        return Ok(());
+    }
+
+    let format = match &extension(target_image_path)[..] {
+        "jpg" | "jpeg" => image::JPEG,
+        _              => image::PNG
+    };
+
+    let img = try!(image::open(source_image_path)
+        .map_err(|err| Error::Decode(source_image_path.to_path_buf(), err)));
+
+    let (width, height)   = img.dimensions();
+    let (nwidth, nheight) = thumb_dimensions(width, height, size);
+    let resized_img       = img.resize(nwidth, nheight, filter);
+
+    let ref mut fout = try!(File::create(target_image_path)
+        .map_err(|err| Error::Write(target_image_path.to_path_buf(), err)));
 
-    let ref mut fout = File::create(target_image_path)
-        .ok()
-        .expect(&format!("Cannot create file {:?}", target_image_path));
+    try!(resized_img.save(fout, format)
+        .map_err(|err| Error::Encode(target_image_path.to_path_buf(), err)));
 
-    let _  = resized_img.save(fout, image::PNG)
-        .ok()
-        .expect(&format!("Cannot save image to {:?}", target_image_path));
+    Ok(())
+}
+
+/// Keep the aspect ratio and never make the thumbnail bigger than
+/// the original.
+fn thumb_dimensions(width: u32, height: u32, size: ThumbnailSize) -> (u32, u32) {
+    let (width_f, height_f) = (width as f64, height as f64);
+
+    let ratio = match size {
+        ThumbnailSize::Scale(scale)               => scale,
+        ThumbnailSize::Width(nwidth)              => nwidth as f64 / width_f,
+        ThumbnailSize::Height(nheight)            => nheight as f64 / height_f,
+        ThumbnailSize::Fit(max_width, max_height) => (max_width as f64 / width_f).min(max_height as f64 / height_f),
+    }.min(1.0);
+
+    let nwidth  = (width_f * ratio).round().max(1.0) as u32;
+    let nheight = (height_f * ratio).round().max(1.0) as u32;
+
+    (nwidth, nheight)
+}
+
+/// Guess the real format from the first bytes of the file, regardless
+/// of its extension.
+pub fn sniff_format(image_path: &Path) -> Option<&'static str> {
+    let header = read_header(image_path, 256);
+    let text   = String::from_utf8_lossy(&header);
+    let text   = text.trim_left();
+
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if header.starts_with(b"\xff\xd8\xff") {
+        Some("jpeg")
+    } else if header.starts_with(b"GIF8") {
+        Some("gif")
+    } else if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        Some("webp")
+    } else if text.starts_with("<?xml") || text.starts_with("<svg") {
+        Some("svg")
+    } else {
+        None
+    }
+}
 
-    print!(".");
-    // println!("{:?} -> {:?}", source_image_path, target_image_path);
-    let _ = io::stdout().flush();
+/// Width and height in pixels, read from the file header without decoding
+/// the whole image. `None` for files we do not understand.
+pub fn dimensions(image_path: &Path) -> Option<(u32, u32)> {
+    match sniff_format(image_path) {
+        Some("png")  => png_dimensions(&read_header(image_path, 24)),
+        Some("gif")  => gif_dimensions(&read_header(image_path, 10)),
+        Some("jpeg") => jpeg_dimensions(&read_header(image_path, 64 * 1024)),
+        Some("webp") => webp_dimensions(&read_header(image_path, 30)),
+        Some("svg")  => svg_dimensions(&read_header(image_path, 16 * 1024)),
+        _            => None
+    }
+}
+
+fn png_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    if header.len() < 24 || &header[12..16] != b"IHDR" {
+        return None;
+    }
+
+    Some((be_u32(&header[16..20]), be_u32(&header[20..24])))
+}
+
+fn gif_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    if header.len() < 10 {
+        return None;
+    }
+
+    Some((le_u32(&header[6..8]), le_u32(&header[8..10])))
+}
+
+/// Walk the markers until a start of frame (SOF0 to SOF15 without DHT,
+/// JPG and DAC) is found.
+fn jpeg_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    let mut index = 2;
+
+    while index + 9 < header.len() {
+        if header[index] != 0xff {
+            return None;
+        }
+
+        let marker = header[index + 1];
+        let length = be_u32(&header[index + 2..index + 4]) as usize;
+
+        if marker >= 0xc0 && marker <= 0xcf && marker != 0xc4 && marker != 0xc8 && marker != 0xcc {
+            let height = be_u32(&header[index + 5..index + 7]);
+            let width  = be_u32(&header[index + 7..index + 9]);
+
+            return Some((width, height));
+        }
+
+        index += 2 + length;
+    }
+
+    None
+}
+
+/// Lossy (`VP8 `), lossless (`VP8L`) and extended (`VP8X`) WebP.
+fn webp_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    if header.len() < 30 {
+        return None;
+    }
+
+    let chunk = &header[12..16];
+
+    if chunk == b"VP8 " {
+        Some((le_u32(&header[26..28]) & 0x3fff, le_u32(&header[28..30]) & 0x3fff))
+    } else if chunk == b"VP8L" {
+        let bits = le_u32(&header[21..25]);
+        Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+    } else if chunk == b"VP8X" {
+        Some((le_u32(&header[24..27]) + 1, le_u32(&header[27..30]) + 1))
+    } else {
+        None
+    }
+}
+
+/// `width` and `height` of the root element when both are in pixels,
+/// otherwise the size of its `viewBox`.
+fn svg_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    let text = String::from_utf8_lossy(header);
+
+    let start = match text.find("<svg") {
+        Some(start) => start,
+        None        => return None
+    };
+
+    let tag = match text[start..].find('>') {
+        Some(end) => &text[start..start + end],
+        None      => return None
+    };
+
+    let pixels = |name: &str| {
+        svg_attribute(tag, name)
+            .map(|value| value.trim().trim_right_matches("px"))
+            .and_then(|value| value.parse::<f64>().ok())
+            .and_then(|value| if value > 0.0 { Some(value) } else { None })
+    };
+
+    if let (Some(width), Some(height)) = (pixels("width"), pixels("height")) {
+        return Some((width.round().max(1.0) as u32, height.round().max(1.0) as u32));
+    }
+
+    let view_box = svg_attribute(tag, "viewBox")
+        .map(|value| {
+            value.split(|ch: char| ch.is_whitespace() || ch == ',')
+                .filter(|number| !number.is_empty())
+                .filter_map(|number| number.parse::<f64>().ok())
+                .collect::<Vec<f64>>()
+        })
+        .unwrap_or(Vec::new());
+
+    if view_box.len() == 4 && view_box[2] > 0.0 && view_box[3] > 0.0 {
+        Some((view_box[2].round().max(1.0) as u32, view_box[3].round().max(1.0) as u32))
+    } else {
+        None
+    }
+}
+
+/// Value of the attribute `name` in a start tag, quoted either way.
+fn svg_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+
+    while let Some(index) = rest.find(name) {
+        let preceded = rest[..index].chars().last().map_or(false, char::is_whitespace);
+        rest         = &rest[index + name.len()..];
+        let value    = rest.trim_left();
+
+        if !preceded || !value.starts_with('=') {
+            continue;
+        }
+
+        let value = value[1..].trim_left();
+
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _                                            => return None
+        };
+
+        return value[1..].find(quote).map(|end| &value[1..end + 1]);
+    }
+
+    None
+}
+
+fn read_header(image_path: &Path, length: u64) -> Vec<u8> {
+    let mut header = Vec::new();
+
+    if let Ok(file) = File::open(image_path) {
+        let _ = file.take(length).read_to_end(&mut header);
+    }
+
+    header
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |number, byte| (number << 8) | *byte as u32)
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |number, byte| (number << 8) | *byte as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        gif_dimensions,
+        jpeg_dimensions,
+        png_dimensions,
+        svg_dimensions,
+        webp_dimensions,
+    };
+
+    fn png() -> Vec<u8> {
+        let mut header = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        header.extend([0, 0, 0x01, 0x77, 0, 0, 0x03, 0x2c].iter());
+        header
+    }
+
+    fn gif() -> Vec<u8> {
+        let mut header = b"GIF89a".to_vec();
+        header.extend([0x77, 0x01, 0x2c, 0x03].iter());
+        header
+    }
+
+    /// An APP0 segment before the start of frame.
+    fn jpeg() -> Vec<u8> {
+        let mut header = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10];
+        header.extend([0; 14].iter());
+        header.extend([0xff, 0xc0, 0x00, 0x11, 0x08, 0x03, 0x2c, 0x01, 0x77, 0x03].iter());
+        header
+    }
+
+    fn webp(chunk: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut header = b"RIFF\x00\x00\x00\x00WEBP".to_vec();
+        header.extend(chunk.iter());
+        header.extend([0, 0, 0, 0].iter());
+        header.extend(data.iter());
+        header
+    }
+
+    /// Every shorter header, `None` when it ends before the dimensions.
+    fn assert_truncated<F>(header: &[u8], needed: usize, dimensions: F)
+        where F: Fn(&[u8]) -> Option<(u32, u32)>
+    {
+        for length in 0..header.len() {
+            let result = dimensions(&header[..length]);
+
+            if length < needed {
+                assert_eq!(result, None);
+            }
+        }
+    }
+
+    #[test]
+    fn png_header() {
+        assert_eq!(png_dimensions(&png()), Some((375, 812)));
+        assert_truncated(&png(), 24, png_dimensions);
+    }
+
+    #[test]
+    fn gif_header() {
+        assert_eq!(gif_dimensions(&gif()), Some((375, 812)));
+        assert_truncated(&gif(), 10, gif_dimensions);
+    }
+
+    #[test]
+    fn jpeg_header() {
+        assert_eq!(jpeg_dimensions(&jpeg()), Some((375, 812)));
+        assert_truncated(&jpeg(), 29, jpeg_dimensions);
+        assert_eq!(jpeg_dimensions(&[0xff, 0xd8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn webp_headers() {
+        let lossy    = webp(b"VP8 ", &[0, 0, 0, 0x9d, 0x01, 0x2a, 0x77, 0x01, 0x2c, 0x03]);
+        let lossless = webp(b"VP8L", &[0x2f, 0x76, 0xc1, 0xca, 0x00, 0, 0, 0, 0, 0]);
+        let extended = webp(b"VP8X", &[0, 0, 0, 0, 0x76, 0x01, 0x00, 0x2b, 0x03, 0x00]);
+
+        assert_eq!(webp_dimensions(&lossy), Some((375, 812)));
+        assert_eq!(webp_dimensions(&lossless), Some((375, 812)));
+        assert_eq!(webp_dimensions(&extended), Some((375, 812)));
+
+        assert_truncated(&lossy, 30, webp_dimensions);
+        assert_truncated(&lossless, 30, webp_dimensions);
+        assert_truncated(&extended, 30, webp_dimensions);
+    }
+
+    #[test]
+    fn svg_size_in_pixels_or_view_box() {
+        assert_eq!(svg_dimensions(b"<?xml version=\"1.0\"?>\n<svg width=\"375px\" height='812'>"), Some((375, 812)));
+        assert_eq!(svg_dimensions(b"<svg viewBox=\"0 0 375 812\" width=\"100%\">"), Some((375, 812)));
+        assert_eq!(svg_dimensions(b"<svg stroke-width=\"2\" viewBox=\"0,0,375.4,811.6\">"), Some((375, 812)));
+    }
+
+    #[test]
+    fn svg_without_a_size() {
+        assert_eq!(svg_dimensions(b""), None);
+        assert_eq!(svg_dimensions(b"<svg width=\"375\" height=\"812\""), None);
+        assert_eq!(svg_dimensions(b"<svg width=\"375"), None);
+        assert_eq!(svg_dimensions(b"<svg width=\"2em\" height=\"2em\">"), None);
+        assert_eq!(svg_dimensions(b"<svg viewBox=\"0 0 0 812\">"), None);
+    }
 }
@@ -0,0 +1,263 @@
+/*
+ * Full-screen viewer for the mockups, written to site/js/mockups.js.
+ *
+ * Screens are the `a.screen` links of a page, grouped into sections by their
+ * `data-section`. Left and right arrows go through the screens of a section,
+ * up and down jump to the previous or next section (on section pages that is
+ * the neighbouring page from the sidebar) and Escape closes the viewer.
+ *
+ * Every screen has its own URL, eg `inbox.html#view=XY-%5Binbox%5D-0.png`,
+ * `#view` alone opens the first screen of the page.
+ */
+(function () {
+  'use strict';
+
+  var KEY_ESCAPE = 27;
+  var KEY_LEFT   = 37;
+  var KEY_UP     = 38;
+  var KEY_RIGHT  = 39;
+  var KEY_DOWN   = 40;
+
+  var screens = [];
+  var current = -1;
+  var overlay, image, caption;
+
+  function init() {
+    var links = document.querySelectorAll('a.screen');
+
+    for (var i = 0; i < links.length; i++) {
+      addScreen(links[i], i);
+    }
+
+    if (!screens.length) {
+      return;
+    }
+
+    build();
+
+    document.addEventListener('keydown', onKeyDown);
+    window.addEventListener('hashchange', fromHash);
+
+    fromHash();
+  }
+
+  function addScreen(link, index) {
+    screens.push({
+      file:    link.getAttribute('data-screen'),
+      section: link.getAttribute('data-section'),
+      title:   link.getAttribute('data-title'),
+      src:     link.getAttribute('href')
+    });
+
+    link.addEventListener('click', function (event) {
+      // Let the browser open the original in a new tab or window.
+      if (event.button !== 0 || event.metaKey || event.ctrlKey || event.shiftKey || event.altKey) {
+        return;
+      }
+
+      event.preventDefault();
+      show(index, true);
+    });
+  }
+
+  function build() {
+    overlay = element('div', 'lightbox');
+    image   = element('img', 'lightbox-image');
+    caption = element('p', 'lightbox-caption');
+
+    var close    = element('button', 'lightbox-close', '×', 'Close (Esc)');
+    var previous = element('button', 'lightbox-previous', '‹', 'Previous (←)');
+    var next     = element('button', 'lightbox-next', '›', 'Next (→)');
+
+    close.addEventListener('click', hide);
+    previous.addEventListener('click', function () { step(-1); });
+    next.addEventListener('click', function () { step(1); });
+
+    // Clicking the backdrop closes, clicking the screen goes on.
+    overlay.addEventListener('click', function (event) {
+      if (event.target === overlay) {
+        hide();
+      }
+    });
+    image.addEventListener('click', function () { step(1); });
+
+    overlay.appendChild(close);
+    overlay.appendChild(previous);
+    overlay.appendChild(image);
+    overlay.appendChild(next);
+    overlay.appendChild(caption);
+    overlay.style.display = 'none';
+
+    document.body.appendChild(overlay);
+  }
+
+  function element(tag, className, text, title) {
+    var node = document.createElement(tag);
+    node.className = className;
+
+    if (text) {
+      node.appendChild(document.createTextNode(text));
+    }
+
+    if (title) {
+      node.title = title;
+      node.type  = 'button';
+    }
+
+    return node;
+  }
+
+  function show(index, remember) {
+    var screen = screens[index];
+    current = index;
+
+    image.src = screen.src;
+    image.alt = screen.title;
+    caption.textContent = screen.title + ' – ' + (sectionPosition(index) + 1) + ' / ' + sectionLength(index);
+
+    overlay.style.display = '';
+    document.body.classList.add('lightbox-open');
+
+    setHash('view=' + encodeURIComponent(screen.file), remember);
+  }
+
+  function hide() {
+    if (current < 0) {
+      return;
+    }
+
+    current = -1;
+    overlay.style.display = 'none';
+    document.body.classList.remove('lightbox-open');
+
+    setHash('', false);
+  }
+
+  /** Pushes a history entry when opening so Back closes the viewer. */
+  function setHash(hash, remember) {
+    var url = location.pathname + location.search + (hash ? '#' + hash : '');
+
+    if (!window.history || !history.replaceState) {
+      location.hash = hash;
+    } else if (remember) {
+      history.pushState(null, '', url);
+    } else {
+      history.replaceState(null, '', url);
+    }
+  }
+
+  /** `#view` opens the first screen, a file which is not there none. */
+  function fromHash() {
+    var match = /^#view(?:=(.*))?$/.exec(location.hash);
+
+    if (!match) {
+      hide();
+      return;
+    }
+
+    if (!match[1]) {
+      show(0, false);
+      return;
+    }
+
+    var file = decode(match[1]);
+
+    for (var i = 0; i < screens.length; i++) {
+      if (screens[i].file === file) {
+        show(i, false);
+        return;
+      }
+    }
+
+    hide();
+  }
+
+  /** `null` for a malformed escape, eg `%E0`. */
+  function decode(component) {
+    try {
+      return decodeURIComponent(component);
+    } catch (error) {
+      return null;
+    }
+  }
+
+  function onKeyDown(event) {
+    if (current < 0 || event.metaKey || event.ctrlKey || event.altKey) {
+      return;
+    }
+
+    switch (event.keyCode) {
+      case KEY_ESCAPE: hide();          break;
+      case KEY_LEFT:   step(-1);        break;
+      case KEY_RIGHT:  step(1);         break;
+      case KEY_UP:     jumpSection(-1); break;
+      case KEY_DOWN:   jumpSection(1);  break;
+      default:         return;
+    }
+
+    event.preventDefault();
+  }
+
+  /** Next or previous screen of the same section, stays at the ends. */
+  function step(direction) {
+    var index = current + direction;
+
+    if (screens[index] && screens[index].section === screens[current].section) {
+      show(index, false);
+    }
+  }
+
+  /** First screen of the next or previous section. */
+  function jumpSection(direction) {
+    var start = sectionStart(current);
+    var index = direction > 0 ? start + sectionLength(current) : start - 1;
+
+    if (screens[index]) {
+      show(sectionStart(index), false);
+      return;
+    }
+
+    // Only one section on this page, go to the neighbouring section page,
+    // skipping group headings.
+    var sibling = document.querySelector('.sections-menu li.selected');
+    var link    = null;
+
+    while (sibling && !link) {
+      sibling = direction > 0 ? sibling.nextElementSibling : sibling.previousElementSibling;
+      link    = sibling && sibling.querySelector('a');
+    }
+
+    if (link) {
+      location.href = link.href + '#view';
+    }
+  }
+
+  function sectionStart(index) {
+    while (index > 0 && screens[index - 1].section === screens[index].section) {
+      index--;
+    }
+
+    return index;
+  }
+
+  function sectionLength(index) {
+    var start = sectionStart(index);
+    var end   = start;
+
+    while (screens[end + 1] && screens[end + 1].section === screens[start].section) {
+      end++;
+    }
+
+    return end - start + 1;
+  }
+
+  function sectionPosition(index) {
+    return index - sectionStart(index);
+  }
+
+  if (document.readyState === 'loading') {
+    document.addEventListener('DOMContentLoaded', init);
+  } else {
+    init();
+  }
+})();
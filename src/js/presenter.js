@@ -0,0 +1,89 @@
+/*
+ * Presenter for a flow, written to site/js/presenter.js and used by the
+ * flows/*.present.html pages.
+ *
+ * One step is shown at a time, the one in the URL hash (`#3`) or the first
+ * one. Right arrow, space and a click on the screen go to the next step,
+ * left arrow to the previous one, Home and End to the first and the last.
+ * Escape goes back to the storyboard.
+ */
+(function () {
+  'use strict';
+
+  var KEY_ESCAPE    = 27;
+  var KEY_SPACE     = 32;
+  var KEY_PAGE_UP   = 33;
+  var KEY_PAGE_DOWN = 34;
+  var KEY_END       = 35;
+  var KEY_HOME      = 36;
+  var KEY_LEFT      = 37;
+  var KEY_RIGHT     = 39;
+
+  var steps, exit;
+  var current = 0;
+
+  function init() {
+    steps = document.querySelectorAll('.presenter-step');
+    exit  = document.querySelector('.presenter-exit');
+
+    if (!steps.length) {
+      return;
+    }
+
+    for (var i = 0; i < steps.length; i++) {
+      steps[i].addEventListener('click', function () { show(current + 1); });
+    }
+
+    document.addEventListener('keydown', onKeyDown);
+    window.addEventListener('hashchange', fromHash);
+
+    fromHash();
+  }
+
+  function fromHash() {
+    var step = parseInt(location.hash.slice(1), 10);
+
+    show(isNaN(step) ? 0 : step - 1);
+  }
+
+  /** Stays at the first and the last step. */
+  function show(index) {
+    current = Math.max(0, Math.min(index, steps.length - 1));
+
+    for (var i = 0; i < steps.length; i++) {
+      steps[i].classList.toggle('current', i === current);
+    }
+
+    var url = location.pathname + location.search + '#' + (current + 1);
+
+    if (window.history && history.replaceState) {
+      history.replaceState(null, '', url);
+    }
+  }
+
+  function onKeyDown(event) {
+    if (event.metaKey || event.ctrlKey || event.altKey) {
+      return;
+    }
+
+    switch (event.keyCode) {
+      case KEY_RIGHT:
+      case KEY_SPACE:
+      case KEY_PAGE_DOWN: show(current + 1);         break;
+      case KEY_LEFT:
+      case KEY_PAGE_UP:   show(current - 1);         break;
+      case KEY_HOME:      show(0);                   break;
+      case KEY_END:       show(steps.length - 1);    break;
+      case KEY_ESCAPE:    location.href = exit.href; break;
+      default:            return;
+    }
+
+    event.preventDefault();
+  }
+
+  if (document.readyState === 'loading') {
+    document.addEventListener('DOMContentLoaded', init);
+  } else {
+    init();
+  }
+})();
@@ -0,0 +1,87 @@
+/*
+ * Clickable prototype, written to site/js/prototype.js and used by the
+ * index.prototype.html page of a category.
+ *
+ * One screen is shown at a time, the one in the URL hash or the first one.
+ * Hotspots are plain links to other screens, so Back goes to the previous
+ * screen. The toggle (or H) highlights all hotspots, clicking outside of
+ * them flashes them briefly.
+ */
+(function () {
+  'use strict';
+
+  var KEY_H = 72;
+
+  var screens, title, toggle;
+
+  function init() {
+    screens = document.querySelectorAll('.prototype-screen');
+    title   = document.querySelector('.prototype-title');
+    toggle  = document.querySelector('.prototype-toggle');
+
+    if (!screens.length) {
+      return;
+    }
+
+    toggle.addEventListener('click', toggleHotspots);
+
+    document.addEventListener('keydown', function (event) {
+      if (event.keyCode === KEY_H && !event.metaKey && !event.ctrlKey && !event.altKey) {
+        toggleHotspots();
+      }
+    });
+
+    for (var i = 0; i < screens.length; i++) {
+      screens[i].addEventListener('click', flashHotspots);
+    }
+
+    window.addEventListener('hashchange', fromHash);
+
+    fromHash();
+  }
+
+  /** The first screen for an unknown or malformed hash. */
+  function fromHash() {
+    var id     = decode(location.hash.slice(1));
+    var screen = (id && document.getElementById(id)) || screens[0];
+
+    for (var i = 0; i < screens.length; i++) {
+      screens[i].classList.toggle('current', screens[i] === screen);
+    }
+
+    title.textContent = screen.getAttribute('data-title');
+    window.scrollTo(0, 0);
+  }
+
+  /** `null` for a malformed escape, eg `%E0`. */
+  function decode(component) {
+    try {
+      return decodeURIComponent(component);
+    } catch (error) {
+      return null;
+    }
+  }
+
+  function toggleHotspots() {
+    var shown = document.body.classList.toggle('show-hotspots');
+    toggle.textContent = shown ? 'Hide hotspots' : 'Show hotspots';
+  }
+
+  function flashHotspots(event) {
+    if (event.target.classList.contains('hotspot')) {
+      return;
+    }
+
+    document.body.classList.add('flash-hotspots');
+
+    setTimeout(function () {
+      document.body.classList.remove('flash-hotspots');
+    }, 400);
+  }
+
+  if (document.readyState === 'loading') {
+    document.addEventListener('DOMContentLoaded', init);
+  } else {
+    init();
+  }
+})();
@@ -0,0 +1,210 @@
+//! Walk down a project directory and generate a static HTML site containing
+//! resized images.
+//!
+//! ## File structure before site is generated
+//!
+//! ```
+//! Project Name
+//!   |- mockups
+//!   |    +- icon.png
+//!   |- iphone-portrait
+//!   |    |- XY-[section-a]-0.png
+//!   |    +- XY-[section-a]-1.png
+//!   |- iphone-landscape
+//!   |    |- XY-[section-a]-0.png
+//!   |    +- XY-[section-b]-0.png
+//!   |- ipad-portrait
+//!   |    +- XY-[section-b]-0.png
+//!   +- ipad-landscape
+//!        |- XY-[section-a]-0.png
+//!        +- XY-[section-b]-0.png
+//! ```
+//!
+//! ## File structure after site is generated
+//!
+//! ```
+//! Project Name
+//!   |- mockups
+//!   |    +- icon.png
+//!   |- iphone-portrait
+//!   |    |- XY-[section-a]-0.png
+//!   |    +- XY-[section-a]-1.png
+//!   |- iphone-landscape
+//!   |    |- XY-[section-a]-0.png
+//!   |    +- XY-[section-b]-0.png
+//!   |- ipad-portrait
+//!   |    +- XY-[section-b]-0.png
+//!   |- ipad-landscape
+//!   |    |- XY-[section-a]-0.png
+//!   |    +- XY-[section-b]-0.png
+//!   +- site
+//!        |- thumbs
+//!        |    |- iphone-portrait
+//!        |    |    |- XY-[section-a]-0.png
+//!        |    |    +- XY-[section-a]-1.png
+//!        |    |- iphone-landscape
+//!        |    |    |- XY-[section-a]-0.png
+//!        |    |    +- XY-[section-b]-0.png
+//!        |    |- ipad-portrait
+//!        |    |    +- XY-[section-b]-0.png
+//!        |    +- ipad-landscape
+//!        |         |- XY-[section-a]-0.png
+//!        |         +- XY-[section-b]-0.png
+//!        |- index.html
+//!        |- css
+//!        |    +- styles.css
+//!        |- img
+//!        |    |- icon.png
+//!        |    +- logo.png
+//!        |- js
+//!        |    +- mockups.js
+//!        |- iphone-portrait
+//!        |    |- index.html
+//!        |    +- section-a.html
+//!        |- iphone-landscape
+//!        |    |- index.html
+//!        |    |- section-a.html
+//!        |    +- section-b.html
+//!        |- ipad-portrait
+//!        |    |- index.html
+//!        |    +- section-b.html
+//!        +- ipad-landscape
+//!             |- index.html
+//!             |- section-a.html
+//!             +- section-b.html
+//! ```
+//!
+//! ## Library
+//!
+//! ```
+//! let project_path = Path::new("path/to/Project Name");
+//!
+//! let config       = try!(Config::load(project_path, None));
+//! let project      = try!(Project::scan(project_path, &config));
+//! let mut manifest = Manifest::load(&config.output);
+//!
+//! let pages  = try!(Site::render(&project, &config, &mut manifest));
+//! let thumbs = try!(Thumbnails::generate(&project, &config, &mut manifest));
+//! try!(manifest.save(&config.output));
+//! ```
+//!
+//! Behaviour can be tuned with a `mockups.toml` file, see the `config` module.
+
+extern crate regex;
+extern crate rustc_serialize;
+extern crate url;
+extern crate image;
+extern crate threadpool;
+extern crate mustache;
+extern crate sys_info;
+extern crate toml;
+extern crate time;
+extern crate pulldown_cmark;
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+pub mod config;
+pub mod error;
+pub mod export;
+pub mod lint;
+pub mod serve;
+pub mod structure;
+pub mod watch;
+
+mod flows;
+mod hotspots;
+mod images;
+mod manifest;
+mod notes;
+mod site;
+mod template;
+mod theme;
+mod utils;
+
+pub use config::Config;
+pub use error::{
+    Error,
+    Result,
+};
+pub use manifest::Manifest;
+pub use structure::{
+    Category,
+    Image,
+    Section,
+    SkippedFile,
+};
+
+/// A scanned project directory. Files in the category directories which
+/// are not mockups are in `skipped`, configured categories without a
+/// directory in `missing`.
+pub struct Project {
+    pub path:       PathBuf,
+    pub categories: Vec<Category>,
+    pub skipped:    Vec<SkippedFile>,
+    pub missing:    Vec<String>,
+}
+
+/// What a build step did. For thumbnails `written` counts files and
+/// `unchanged` images, each of which may have several files.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub written:   usize,
+    pub unchanged: usize,
+    pub removed:   usize,
+}
+
+/// The HTML pages, styles and images of the site.
+pub struct Site;
+
+/// Thumbnails and previews of every mockup.
+pub struct Thumbnails;
+
+impl Project {
+    /// Read the category directories of the project. Missing categories
+    /// are left out.
+    pub fn scan(path: &Path, config: &Config) -> Result<Project> {
+        if !utils::is_dir(path) {
+            return Err(Error::NotADirectory(path.to_path_buf()));
+        }
+
+        let mut categories = Vec::new();
+        let mut skipped    = Vec::new();
+        let mut missing    = Vec::new();
+
+        try!(structure::read_directories(path, config, &mut categories, &mut skipped, &mut missing));
+
+        Ok(Project {
+            path:       path.to_path_buf(),
+            categories: categories,
+            skipped:    skipped,
+            missing:    missing,
+        })
+    }
+}
+
+impl Site {
+    /// Write the pages which changed since the build in `manifest` into
+    /// `config.output`. Pages which fail do not stop the others, they are
+    /// returned together in `Error::Build`.
+    pub fn render(project: &Project, config: &Config, manifest: &mut Manifest) -> Result<Stats> {
+        let mut errors = Vec::new();
+        let stats      = site::generate(&project.path, config, &project.categories, manifest, &mut errors);
+
+        Error::from_errors(errors).map(|_| stats)
+    }
+}
+
+impl Thumbnails {
+    /// Resize the images which changed since the build in `manifest`.
+    /// Images which fail do not stop the others, they are returned
+    /// together in `Error::Build`.
+    pub fn generate(project: &Project, config: &Config, manifest: &mut Manifest) -> Result<Stats> {
+        let mut errors = Vec::new();
+        let stats      = images::generate_thumbs(&project.path, config, &project.categories, manifest, &mut errors);
+
+        Error::from_errors(errors).map(|_| stats)
+    }
+}
@@ -0,0 +1,211 @@
+//! Check the scanned project for common mistakes without generating anything.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use rustc_serialize::json;
+
+use config::Config;
+use images;
+
+use structure::{
+    Category,
+    Section,
+    SkippedFile,
+};
+
+/// One problem found in the project. `file` is missing for problems
+/// concerning a whole section.
+#[derive(RustcEncodable)]
+pub struct Issue {
+    pub kind:     String,
+    pub category: String,
+    pub file:     Option<String>,
+    pub message:  String,
+}
+
+#[derive(RustcEncodable)]
+struct Report<'a> {
+    issues: &'a Vec<Issue>,
+}
+
+impl Issue {
+    fn new(kind: &str, category: &str, file: Option<&str>, message: String) -> Issue {
+        Issue {
+            kind:     kind.to_string(),
+            category: category.to_string(),
+            file:     file.map(|file| file.to_string()),
+            message:  message,
+        }
+    }
+}
+
+/// Run all the checks, issues are in the order of the categories.
+pub fn check(project_path: &Path, config: &Config, categories: &Vec<Category>, skipped: &Vec<SkippedFile>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for file in skipped.iter() {
+        issues.push(Issue::new("skipped", &file.category, Some(&file.file), format!("{}", file.reason)));
+    }
+
+    for category in categories.iter() {
+        for section in category.sections.iter() {
+            check_duplicates(category, section, &mut issues);
+            check_gaps(category, section, &mut issues);
+        }
+
+        check_orientations(category, categories, &mut issues);
+        check_files(project_path, config, category, &mut issues);
+    }
+
+    issues
+}
+
+/// `path/file: message` lines followed by a summary.
+pub fn print_text(issues: &Vec<Issue>) {
+    for issue in issues.iter() {
+        match issue.file {
+            Some(ref file) => println!("{}/{}: {}", issue.category, file, issue.message),
+            None           => println!("{}: {}", issue.category, issue.message)
+        }
+    }
+
+    if issues.is_empty() {
+        println!("No issues found");
+    } else {
+        println!("{} issue(s) found", issues.len());
+    }
+}
+
+/// `{"issues": [{"kind": ..., "category": ..., "file": ..., "message": ...}]}`
+pub fn print_json(issues: &Vec<Issue>) {
+    println!("{}", json::as_pretty_json(&Report { issues: issues }));
+}
+
+/// Two files for the same screen, eg `XY-[inbox]-1.png` and `AB-[inbox]-1.png`.
+fn check_duplicates(category: &Category, section: &Section, issues: &mut Vec<Issue>) {
+    let mut seen = HashSet::new();
+
+    for image in section.images.iter() {
+        let key = (image.number, &image.state, &image.variant, &image.version);
+
+        if !seen.insert(key) {
+            issues.push(Issue::new(
+                "duplicate",
+                &category.file,
+                Some(&image.file),
+                format!("section `{}` already has screen {}", section.class, image.label())
+            ));
+        }
+    }
+}
+
+/// Eg screens 0, 1 and 3 are there but 2 is missing. Every run of missing
+/// numbers is one issue, numbers like `20150101` leave huge gaps.
+fn check_gaps(category: &Category, section: &Section, issues: &mut Vec<Issue>) {
+    let mut numbers = section.images.iter().map(|image| image.number).collect::<Vec<u32>>();
+    numbers.sort();
+    numbers.dedup();
+
+    for pair in numbers.windows(2) {
+        let (first, last) = (pair[0] + 1, pair[1] - 1);
+
+        let missing = if first == last {
+            format!("screen {}", first)
+        } else if first < last {
+            format!("screens {} to {}", first, last)
+        } else {
+            continue;
+        };
+
+        issues.push(Issue::new(
+            "gap",
+            &category.file,
+            None,
+            format!("section `{}` has no {}", section.class, missing)
+        ));
+    }
+}
+
+/// Sections in `iphone-portrait` which are not in `iphone-landscape` and
+/// the other way round. Only categories of the same family are compared.
+fn check_orientations(category: &Category, categories: &Vec<Category>, issues: &mut Vec<Issue>) {
+    if category.family.is_empty() || category.orientation.is_none() {
+        return;
+    }
+
+    for other in categories.iter() {
+        if other.family != category.family || other.orientation.is_none() || other.orientation == category.orientation {
+            continue;
+        }
+
+        for section in category.sections.iter() {
+            if !other.sections.iter().any(|other_section| other_section.class == section.class) {
+                issues.push(Issue::new(
+                    "orientation",
+                    &category.file,
+                    None,
+                    format!("section `{}` is missing in {}", section.class, other.file)
+                ));
+            }
+        }
+    }
+}
+
+/// Dimensions, size and real format of every image.
+fn check_files(project_path: &Path, config: &Config, category: &Category, issues: &mut Vec<Issue>) {
+    let max_file_size = config.lint.max_file_size * 1024;
+
+    for section in category.sections.iter() {
+        for image in section.images.iter() {
+            let image_path = project_path.join(&image.category).join(&image.file);
+
+            if let Ok(metadata) = fs::metadata(&image_path) {
+                if metadata.len() > max_file_size {
+                    issues.push(Issue::new(
+                        "oversized",
+                        &category.file,
+                        Some(&image.file),
+                        format!("{} KB is more than {} KB", metadata.len() / 1024, config.lint.max_file_size)
+                    ));
+                }
+            }
+
+            let expected = match &images::extension(&image_path)[..] {
+                "jpg" | "jpeg" => "jpeg",
+                extension      => extension
+            }.to_string();
+
+            match images::sniff_format(&image_path) {
+                Some(format) if format == expected => (),
+                Some(format) => {
+                    issues.push(Issue::new("content", &category.file, Some(&image.file), format!("not a {} image, looks like {}", expected, format)));
+                },
+                None => {
+                    issues.push(Issue::new("content", &category.file, Some(&image.file), format!("not a {} image", expected)));
+                }
+            }
+
+            let (width, height) = match (image.width, image.height) {
+                (Some(width), Some(height)) => (width, height),
+                _                           => continue
+            };
+
+            let mismatch = match category.orientation {
+                Some(ref orientation) if orientation == "portrait"  => width > height,
+                Some(ref orientation) if orientation == "landscape" => height > width,
+                _                                                   => false
+            };
+
+            if mismatch {
+                issues.push(Issue::new(
+                    "dimensions",
+                    &category.file,
+                    Some(&image.file),
+                    format!("{}x{} does not look {}", width, height, category.orientation.clone().unwrap())
+                ));
+            }
+        }
+    }
+}
@@ -1,112 +1,91 @@
-//! Walk down a project directory and generate a static HTML site containing
-//! resized images.
-//!
-//! ## File structure before site is generated
-//!
-//! ```
-//! Project Name
-//!   |- mockups
-//!   |    +- icon.png
-//!   |- iphone-portrait
-//!   |    |- XY-[section-a]-0.png
-//!   |    +- XY-[section-a]-1.png
-//!   |- iphone-landscape
-//!   |    |- XY-[section-a]-0.png
-//!   |    +- XY-[section-b]-0.png
-//!   |- ipad-portrait
-//!   |    +- XY-[section-b]-0.png
-//!   +- ipad-landscape
-//!        |- XY-[section-a]-0.png
-//!        +- XY-[section-b]-0.png
-//! ```
-//!
-//! ## File structure after site is generated
-//!
-//! ```
-//! Project Name
-//!   |- mockups
-//!   |    +- icon.png
-//!   |- iphone-portrait
-//!   |    |- XY-[section-a]-0.png
-//!   |    +- XY-[section-a]-1.png
-//!   |- iphone-landscape
-//!   |    |- XY-[section-a]-0.png
-//!   |    +- XY-[section-b]-0.png
-//!   |- ipad-portrait
-//!   |    +- XY-[section-b]-0.png
-//!   |- ipad-landscape
-//!   |    |- XY-[section-a]-0.png
-//!   |    +- XY-[section-b]-0.png
-//!   +- site
-//!        |- thumbs
-//!        |    |- iphone-portrait
-//!        |    |    |- XY-[section-a]-0.png
-//!        |    |    +- XY-[section-a]-1.png
-//!        |    |- iphone-landscape
-//!        |    |    |- XY-[section-a]-0.png
-//!        |    |    +- XY-[section-b]-0.png
-//!        |    |- ipad-portrait
-//!        |    |    +- XY-[section-b]-0.png
-//!        |    +- ipad-landscape
-//!        |         |- XY-[section-a]-0.png
-//!        |         +- XY-[section-b]-0.png
-//!        |- index.html
-//!        |- css
-//!        |    +- styles.css
-//!        |- img
-//!        |    |- icon.png
-//!        |    +- logo.png
-//!        |- js
-//!        |    +- mockups.js
-//!        |- iphone-portrait
-//!        |    |- index.html
-//!        |    +- section-a.html
-//!        |- iphone-landscape
-//!        |    |- index.html
-//!        |    |- section-a.html
-//!        |    +- section-b.html
-//!        |- ipad-portrait
-//!        |    |- index.html
-//!        |    +- section-b.html
-//!        +- ipad-landscape
-//!             |- index.html
-//!             |- section-a.html
-//!             +- section-b.html
-//! ```
+//! Command line interface of the generator, see the library for what the
+//! generated site looks like.
 //!
 //! ## Usage
 //!
 //! ```
 //! mockups -d "~/path/to/Project Name"
+//! mockups -d "~/path/to/Project Name" --name "Awesome Mail Client"
+//! mockups -d "~/path/to/Project Name" --self-contained
+//! mockups -d "~/Dropbox/Project Name" --out "~/Previews/Project Name"
+//! mockups lint -d "~/path/to/Project Name" --format json
+//! mockups export -d "~/path/to/Project Name" --format json --file screens.json
+//! mockups watch -d "~/path/to/Project Name"
+//! mockups serve -d "~/path/to/Project Name" --port 8080
 //! ```
+//!
+//! Behaviour can be tuned with a `mockups.toml` file, see the `config` module.
 
-#![feature(fs_walk)]
-
-extern crate regex;
 extern crate rustc_serialize;
 extern crate docopt;
-extern crate url;
-extern crate image;
-extern crate threadpool;
-extern crate mustache;
-extern crate sys_info;
+extern crate mockups;
 
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
 use std::path::Path;
 use docopt::Docopt;
 
-mod structure;
-mod site;
-mod images;
-mod utils;
+use mockups::{
+    Config,
+    Manifest,
+    Project,
+    Site,
+    Thumbnails,
+};
+use mockups::error;
+use mockups::export;
+use mockups::lint;
+use mockups::serve;
+use mockups::structure;
+use mockups::watch;
 
 static USAGE: &'static str = "
-Usage: mockups -d <directory>
+Usage: mockups [build] -d <directory> [options]
+       mockups lint -d <directory> [options]
+       mockups export -d <directory> [options]
+       mockups watch -d <directory> [options]
+       mockups serve -d <directory> [options]
        mockups -h | --help
+
+Commands:
+  build                Generate the site, the default.
+  lint                 Check the project for mistakes, generate nothing.
+  export               Print the scanned project, generate nothing.
+  watch                Build, then rebuild whenever the project changes.
+  serve                Watch and serve the site over HTTP with live reload.
+
+Options:
+  -c, --config <file>  Config file, defaults to mockups.toml in the project.
+  -n, --name <name>    App name, defaults to the project directory name.
+  -o, --out <dir>      Output directory, defaults to site in the project.
+  --strict             Fail when any file in a category directory is skipped.
+  --force              Regenerate all pages and thumbnails.
+  --self-contained     Copy originals and the icon into the site directory.
+  --format <format>    Output of lint, text (default) or json, and of
+                       export, json (default).
+  --file <file>        Write the export to a file instead of stdout.
+  -p, --port <port>    Port to serve on [default: 8000].
+  -h, --help           Show this screen.
 ";
 
 #[derive(RustcDecodable)]
 struct Args {
-    arg_directory: String
+    cmd_lint:            bool,
+    cmd_export:          bool,
+    cmd_watch:           bool,
+    cmd_serve:           bool,
+    arg_directory:       String,
+    flag_config:         Option<String>,
+    flag_name:           Option<String>,
+    flag_out:            Option<String>,
+    flag_strict:         bool,
+    flag_force:          bool,
+    flag_self_contained: bool,
+    flag_format:         Option<String>,
+    flag_file:           Option<String>,
+    flag_port:           u16,
 }
 
 fn main() {
@@ -116,14 +95,228 @@ fn main() {
 
     let project_path = Path::new(&args.arg_directory);
 
-    if !utils::is_dir(project_path) {
+    if !fs::metadata(project_path).map(|metadata| metadata.is_dir()).unwrap_or(false) {
         println!("{:?} is not a directory", project_path);
         return
     }
 
-    let mut categories = Vec::new();
+    let config = match load_config(&args, &project_path) {
+        Some(config) => config,
+        None         => std::process::exit(1)
+    };
+
+    if args.cmd_watch {
+        run_watch(&args, &project_path, config, || ());
+        return;
+    }
+
+    if args.cmd_serve {
+        run_serve(&args, &project_path, config);
+        return;
+    }
+
+    let project = match Project::scan(&project_path, &config) {
+        Ok(project) => project,
+        Err(err)    => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.cmd_lint {
+        run_lint(&config, &project, args.flag_format.as_ref().map_or("text", |format| &format[..]));
+    } else if args.cmd_export {
+        run_export(&config, &project, args.flag_format.as_ref().map_or("json", |format| &format[..]), args.flag_file.as_ref());
+    } else if !run_build(&config, &project) {
+        std::process::exit(1);
+    }
+}
+
+/// Config file with command line flags on top. Errors are printed.
+fn load_config(args: &Args, project_path: &Path) -> Option<Config> {
+    let config_path = args.flag_config.as_ref().map(|path| Path::new(path));
+
+    let mut config = match Config::load(project_path, config_path) {
+        Ok(config) => config,
+        Err(err)   => {
+            println!("{}", err);
+            return None;
+        }
+    };
+
+    if let Some(ref name) = args.flag_name {
+        config.app_name = name.clone();
+    }
+
+    if let Some(ref out) = args.flag_out {
+        if let Err(message) = config.set_output(project_path, Path::new(out)) {
+            println!("--out: {}", message);
+            return None;
+        }
+    }
+
+    if args.flag_strict {
+        config.strict = true;
+    }
+
+    if args.flag_self_contained {
+        config.self_contained = true;
+    }
+
+    if args.flag_force {
+        config.force = true;
+    }
+
+    Some(config)
+}
+
+/// Only what changed since the previous build is generated, unless forced.
+/// Returns false when nothing was generated because of `--strict` or when
+/// anything failed, errors are reported after the rest is generated.
+fn run_build(config: &Config, project: &Project) -> bool {
+    report_missing(project);
+    structure::report_skipped(&project.skipped);
+
+    if config.strict && !project.skipped.is_empty() {
+        println!("Some files were skipped, not generating the site (--strict)");
+        return false;
+    }
+
+    // Loaded even when forced, files of the previous build which are gone
+    // now are still removed.
+    let mut manifest = Manifest::load(&config.output);
+
+    let mut errors = Vec::new();
+
+    match Site::render(project, config, &mut manifest) {
+        Ok(pages) => println!("Pages: {} written, {} unchanged, {} removed", pages.written, pages.unchanged, pages.removed),
+        Err(err)  => errors.push(err)
+    }
 
-    structure::read_directories(&project_path, &mut categories);
-    site::generate(&project_path, &categories);
-    images::generate_thumbs(&project_path, &categories);
+    match Thumbnails::generate(project, config, &mut manifest) {
+        Ok(thumbs) => println!("Thumbnails: {} generated, {} images up to date, {} removed", thumbs.written, thumbs.unchanged, thumbs.removed),
+        Err(err)   => errors.push(err)
+    }
+
+    if let Err(err) = manifest.save(&config.output) {
+        errors.push(err);
+    }
+
+    error::report(&errors);
+
+    errors.is_empty()
+}
+
+/// Categories from the config without a directory in the project.
+fn report_missing(project: &Project) {
+    for dir in project.missing.iter() {
+        let _ = writeln!(io::stderr(), "{:?} not found", project.path.join(dir));
+    }
+}
+
+/// Build once and then after every change. A changed icon only needs the
+/// pages, anything else goes through the (incremental) full build.
+/// `rebuilt` is called after every rebuild. Only the first build is forced.
+fn run_watch<F>(args: &Args, project_path: &Path, config: Config, mut rebuilt: F) where F: FnMut() {
+    let config_path = args.flag_config.as_ref().map(|path| Path::new(path));
+
+    match Project::scan(project_path, &config) {
+        Ok(project) => { run_build(&config, &project); },
+        Err(err)    => println!("{}", err)
+    }
+
+    let config = Config { force: false, ..config };
+    let load   = || load_config(args, project_path).map(|config| Config { force: false, ..config });
+
+    println!("Watching {:?} for changes, press Ctrl+C to stop", project_path);
+
+    watch::watch(project_path, config_path, config, load, |config, changes| {
+        let project = match Project::scan(project_path, config) {
+            Ok(project) => project,
+            Err(err)    => {
+                println!("{}", err);
+                return;
+            }
+        };
+
+        if changes.only_icon() {
+            let mut manifest = Manifest::load(&config.output);
+            let mut errors   = Vec::new();
+
+            if let Err(err) = Site::render(&project, config, &mut manifest) {
+                errors.push(err);
+            }
+
+            if let Err(err) = manifest.save(&config.output) {
+                errors.push(err);
+            }
+
+            error::report(&errors);
+
+            println!("Rebuilt pages");
+        } else if run_build(config, &project) {
+            println!("Rebuilt pages and thumbnails");
+        }
+
+        rebuilt();
+    });
+}
+
+/// Watch, serving the project directory so that the links from the site to
+/// the original images work. Open pages reload after every rebuild.
+fn run_serve(args: &Args, project_path: &Path, config: Config) {
+    let mut allowed = config.categories.iter()
+        .map(|category| project_path.join(&category.dir))
+        .collect::<Vec<_>>();
+    allowed.push(project_path.join("mockups"));
+
+    let reload = serve::LiveReload::new();
+
+    if let Err(err) = serve::serve(project_path, &config.output, allowed, args.flag_port, reload.clone()) {
+        println!("Cannot serve on port {}: {}", args.flag_port, err);
+        std::process::exit(1);
+    }
+
+    run_watch(args, project_path, config, || reload.notify());
+}
+
+/// Exits with 1 when there are any issues so CI can fail the build.
+fn run_lint(config: &Config, project: &Project, format: &str) {
+    let issues = lint::check(&project.path, config, &project.categories, &project.skipped);
+
+    match format {
+        "json" => lint::print_json(&issues),
+        "text" => lint::print_text(&issues),
+        _      => {
+            println!("Unknown format {:?}, use text or json", format);
+            std::process::exit(1);
+        }
+    }
+
+    if !issues.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// The project model for other tools, eg dashboards. JSON is the only
+/// format so far.
+fn run_export(config: &Config, project: &Project, format: &str, file: Option<&String>) {
+    if format != "json" {
+        println!("Unknown format {:?}, use json", format);
+        std::process::exit(1);
+    }
+
+    let json = export::to_json(project, config);
+
+    match file {
+        None       => println!("{}", json),
+        Some(file) => {
+            let written = File::create(file).and_then(|mut file| file.write_all(json.as_bytes()));
+
+            if let Err(err) = written {
+                println!("Cannot write {}: {}", file, err);
+                std::process::exit(1);
+            }
+        }
+    }
 }
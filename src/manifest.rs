@@ -0,0 +1,137 @@
+//! Build manifest kept in the output directory, so that unchanged
+//! thumbnails and pages are not generated again.
+//!
+//! Images are keyed by `category/file`, pages by their path relative
+//! to the output directory.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::hash::{
+    Hasher,
+    SipHasher,
+};
+use std::io;
+use std::io::{
+    Read,
+    Write,
+};
+use std::path::Path;
+
+use rustc_serialize::json;
+
+use error::{
+    Error,
+    Result,
+};
+
+use utils;
+
+static MANIFEST_FILE: &'static str = ".mockups-manifest.json";
+
+/// Bump when the format changes, older manifests are then ignored.
+static MANIFEST_VERSION: u32 = 1;
+
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct Manifest {
+    pub version: u32,
+    pub images:  BTreeMap<String, ImageEntry>,
+    pub pages:   BTreeMap<String, String>,
+}
+
+/// What a source image looked like when its variants were generated.
+/// `settings` is the fingerprint of the thumbnail settings used.
+#[derive(RustcEncodable, RustcDecodable, Clone)]
+pub struct ImageEntry {
+    pub size:     u64,
+    pub mtime:    u64,
+    pub hash:     String,
+    pub settings: String,
+    pub variants: Vec<String>,
+}
+
+impl Manifest {
+    pub fn new() -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            images:  BTreeMap::new(),
+            pages:   BTreeMap::new(),
+        }
+    }
+
+    /// An empty manifest when there is none yet or it cannot be read,
+    /// which simply means everything gets built.
+    pub fn load(output_path: &Path) -> Manifest {
+        let mut contents = String::new();
+
+        if File::open(output_path.join(MANIFEST_FILE)).and_then(|mut file| file.read_to_string(&mut contents)).is_err() {
+            return Manifest::new();
+        }
+
+        match json::decode::<Manifest>(&contents) {
+            Ok(ref manifest) if manifest.version != MANIFEST_VERSION => Manifest::new(),
+            Ok(manifest)                                             => manifest,
+            Err(_)                                                   => Manifest::new()
+        }
+    }
+
+    pub fn save(&self, output_path: &Path) -> Result<()> {
+        let manifest_path = output_path.join(MANIFEST_FILE);
+
+        // Only maps of strings and numbers, encoding does not fail.
+        let contents = json::encode(self).unwrap();
+
+        File::create(&manifest_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|err| Error::Write(manifest_path.clone(), err))
+    }
+}
+
+impl ImageEntry {
+    /// Size and modification time of the source, the content hash is only
+    /// computed when those differ from the previous build.
+    pub fn read(image_path: &Path, settings: &str, variants: Vec<String>, previous: Option<&ImageEntry>) -> Result<ImageEntry> {
+        let metadata = try!(fs::metadata(image_path).map_err(|err| Error::Read(image_path.to_path_buf(), err)));
+
+        let size  = metadata.len();
+        let mtime = utils::mtime(&metadata);
+
+        let hash = match previous {
+            Some(previous) if previous.size == size && previous.mtime == mtime => previous.hash.clone(),
+            _                                                                 => {
+                try!(hash_file(image_path).map_err(|err| Error::Read(image_path.to_path_buf(), err)))
+            }
+        };
+
+        Ok(ImageEntry {
+            size:     size,
+            mtime:    mtime,
+            hash:     hash,
+            settings: settings.to_string(),
+            variants: variants,
+        })
+    }
+
+    /// Same content made with the same settings into the same files.
+    pub fn is_fresh(&self, previous: &ImageEntry, output_path: &Path) -> bool {
+        self.hash == previous.hash &&
+            self.settings == previous.settings &&
+            self.variants == previous.variants &&
+            self.variants.iter().all(|variant| utils::is_file(&output_path.join(variant)))
+    }
+}
+
+/// Hex-encoded SipHash, good enough to notice a changed export.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = SipHasher::new();
+    hasher.write(bytes);
+
+    format!("{:016x}", hasher.finish())
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut contents = Vec::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_end(&mut contents)));
+
+    Ok(hash_bytes(&contents))
+}
@@ -0,0 +1,215 @@
+//! Notes are Markdown files next to the mockups, describing behaviour, edge
+//! cases or acceptance criteria. `[inbox].md` is about the `inbox` section
+//! and shown above it, `XY-[inbox]-0.md` is about one screen and shown under
+//! it.
+//!
+//! Section notes may start with TOML front matter between `+++` lines,
+//! which sets what `[sections]` in `mockups.toml` does for this category:
+//!
+//! ```
+//! +++
+//! name  = "FAQ"
+//! order = 10
+//! group = "Help"
+//! +++
+//!
+//! Answers are collapsed until tapped.
+//! ```
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use pulldown_cmark::{
+    html,
+    Parser,
+};
+use rustc_serialize::Decodable;
+use toml;
+
+use config::SectionConfig;
+use error::{
+    Error,
+    Result,
+};
+use structure::Category;
+use utils;
+
+/// Rendered notes by category directory and section class or image file.
+pub struct Notes {
+    pub sections: BTreeMap<(String, String), String>,
+    pub images:   BTreeMap<(String, String), String>,
+}
+
+impl Notes {
+    pub fn section(&self, category_dir: &str, section_class: &str) -> Option<&String> {
+        self.sections.get(&(category_dir.to_string(), section_class.to_string()))
+    }
+
+    pub fn image(&self, category_dir: &str, image_file: &str) -> Option<&String> {
+        self.images.get(&(category_dir.to_string(), image_file.to_string()))
+    }
+}
+
+/// Read and render the notes of all sections and screens. Files which
+/// cannot be read are collected in `errors`.
+pub fn load(project_path: &Path, categories: &Vec<Category>, errors: &mut Vec<Error>) -> Notes {
+    let mut notes = Notes {
+        sections: BTreeMap::new(),
+        images:   BTreeMap::new(),
+    };
+
+    for category in categories.iter() {
+        let category_path = project_path.join(&category.file);
+
+        for section in category.sections.iter() {
+            let key = (category.file.clone(), section.class.clone());
+
+            if let Some(html) = read_notes(&category_path.join(section_notes_file(&section.class)), errors) {
+                notes.sections.insert(key, html);
+            }
+
+            for image in section.images.iter() {
+                let key = (category.file.clone(), image.file.clone());
+
+                if let Some(html) = read_notes(&category_path.join(image_notes_file(&image.file)), errors) {
+                    notes.images.insert(key, html);
+                }
+            }
+        }
+    }
+
+    notes
+}
+
+/// `inbox -> [inbox].md`
+pub fn section_notes_file(section_class: &str) -> String {
+    format!("[{}].md", section_class)
+}
+
+/// `XY-[inbox]-0.png -> XY-[inbox]-0.md`
+pub fn image_notes_file(image_file: &str) -> String {
+    format!("{}.md", utils::file_stem(image_file))
+}
+
+/// Settings from the front matter of a section's notes, `None` when it has
+/// none.
+pub fn front_matter(notes_path: &Path) -> Result<Option<SectionConfig>> {
+    let contents = try!(utils::read_file(notes_path));
+
+    let front_matter = match split_front_matter(&contents) {
+        (Some(front_matter), _) => front_matter,
+        (None, _)               => return Ok(None)
+    };
+
+    let mut parser = toml::Parser::new(front_matter);
+
+    // The first line of the file is `+++`.
+    let table = match parser.parse() {
+        Some(table) => table,
+        None        => return Err(Error::Notes(notes_path.to_path_buf(), utils::toml_errors(&parser, 2)))
+    };
+
+    let mut decoder = toml::Decoder::new(toml::Value::Table(table));
+
+    let settings: SectionConfig = try!(Decodable::decode(&mut decoder)
+        .map_err(|err| Error::Notes(notes_path.to_path_buf(), format!("{}", err))));
+
+    if let Some(toml::Value::Table(ref leftover)) = decoder.toml {
+        if let Some(key) = leftover.keys().next() {
+            return Err(Error::Notes(notes_path.to_path_buf(), format!("`{}`: unknown key, use name, order or group", key)));
+        }
+    }
+
+    if let Err(message) = settings.validate() {
+        return Err(Error::Notes(notes_path.to_path_buf(), message));
+    }
+
+    Ok(Some(settings))
+}
+
+/// Markdown to HTML, raw HTML in the notes is kept as it is.
+pub fn render(markdown: &str) -> String {
+    let mut html = String::new();
+    html::push_html(&mut html, Parser::new(markdown));
+
+    html
+}
+
+/// `None` when there is no such file or it cannot be read.
+fn read_notes(notes_path: &Path, errors: &mut Vec<Error>) -> Option<String> {
+    if !utils::is_file(notes_path) {
+        return None;
+    }
+
+    match utils::read_file(notes_path) {
+        Ok(contents) => Some(render(split_front_matter(&contents).1)),
+        Err(err)     => {
+            errors.push(err);
+            None
+        }
+    }
+}
+
+/// Front matter and the Markdown after it. The front matter is between a
+/// `+++` first line and the next `+++` line.
+fn split_front_matter(contents: &str) -> (Option<&str>, &str) {
+    let start = match contents.find('\n') {
+        Some(index) if contents[..index].trim_right() == "+++" => index + 1,
+        _                                                      => return (None, contents)
+    };
+
+    let mut offset = start;
+
+    for line in contents[start..].split('\n') {
+        let next = offset + line.len() + 1;
+
+        if line.trim_right() == "+++" {
+            return (Some(&contents[start..offset]), &contents[cmp::min(next, contents.len())..]);
+        }
+
+        offset = next;
+    }
+
+    (None, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_front_matter;
+
+    #[test]
+    fn front_matter_is_split_off() {
+        let contents = "+++\nname = \"FAQ\"\norder = 10\n+++\n\nAnswers are collapsed.\n";
+
+        assert_eq!(split_front_matter(contents), (Some("name = \"FAQ\"\norder = 10\n"), "\nAnswers are collapsed.\n"));
+    }
+
+    #[test]
+    fn front_matter_with_windows_line_endings() {
+        let contents = "+++\r\norder = 1\r\n+++\r\nText";
+
+        assert_eq!(split_front_matter(contents), (Some("order = 1\r\n"), "Text"));
+    }
+
+    #[test]
+    fn front_matter_at_the_end_of_the_file() {
+        assert_eq!(split_front_matter("+++\norder = 1\n+++"), (Some("order = 1\n"), ""));
+        assert_eq!(split_front_matter("+++\n+++\n"), (Some(""), ""));
+    }
+
+    #[test]
+    fn no_front_matter() {
+        assert_eq!(split_front_matter("Just notes.\n"), (None, "Just notes.\n"));
+        assert_eq!(split_front_matter("Text\n+++\nmore"), (None, "Text\n+++\nmore"));
+        assert_eq!(split_front_matter(""), (None, ""));
+        assert_eq!(split_front_matter("+++"), (None, "+++"));
+    }
+
+    #[test]
+    fn unclosed_front_matter_is_markdown() {
+        let contents = "+++\norder = 1\nNo closing line.\n";
+
+        assert_eq!(split_front_matter(contents), (None, contents));
+    }
+}
@@ -0,0 +1,406 @@
+//! Tiny HTTP server for previewing the site on the local network, see
+//! `mockups serve`.
+//!
+//! The common parent of the project and the output directory is served as
+//! the root, eg the project when the site is in `site/`, so the relative
+//! links to the original images and the icon keep working. Only the site,
+//! the category directories and the `mockups` directory are reachable.
+//! HTML pages get a small script injected which reloads them after every
+//! rebuild.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{
+    BufRead,
+    BufReader,
+    Read,
+    Write,
+};
+use std::net::{
+    TcpListener,
+    TcpStream,
+};
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+use std::sync::{
+    Arc,
+    Condvar,
+    Mutex,
+};
+use std::thread;
+use std::time::Duration;
+
+use url::percent_encoding::{
+    FORM_URLENCODED_ENCODE_SET,
+    lossy_utf8_percent_decode,
+    utf8_percent_encode,
+};
+
+use utils;
+
+static LIVE_RELOAD_PATH: &'static str = "/__livereload";
+
+/// How often an idle live reload stream gets a comment, which is how a
+/// closed page is noticed.
+static KEEPALIVE_SECS: u64 = 15;
+
+/// How long a client may take to send its request.
+static REQUEST_TIMEOUT_SECS: u64 = 10;
+
+static LIVE_RELOAD_SCRIPT: &'static str = "<script>
+  new EventSource(\"/__livereload\").onmessage = function () { location.reload(); };
+</script>
+";
+
+/// Counts rebuilds, browsers waiting on `/__livereload` are woken up
+/// whenever it changes.
+#[derive(Clone)]
+pub struct LiveReload {
+    state: Arc<(Mutex<u64>, Condvar)>,
+}
+
+/// What may be served, all paths are absolute.
+struct Roots {
+    root_path: PathBuf,
+    site_url:  String,
+    allowed:   Vec<PathBuf>,
+}
+
+impl LiveReload {
+    pub fn new() -> LiveReload {
+        LiveReload {
+            state: Arc::new((Mutex::new(0), Condvar::new())),
+        }
+    }
+
+    /// Tell every open page to reload.
+    pub fn notify(&self) {
+        let &(ref lock, ref condvar) = &*self.state;
+
+        let mut generation = lock.lock().unwrap();
+        *generation += 1;
+        condvar.notify_all();
+    }
+
+    /// The generation after `seen`, `None` when there was no rebuild within
+    /// `timeout`.
+    fn wait(&self, seen: u64, timeout: Duration) -> Option<u64> {
+        let &(ref lock, ref condvar) = &*self.state;
+
+        let mut generation = lock.lock().unwrap();
+
+        if *generation == seen {
+            generation = condvar.wait_timeout(generation, timeout).unwrap().0;
+        }
+
+        if *generation == seen {
+            None
+        } else {
+            Some(*generation)
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        *self.state.0.lock().unwrap()
+    }
+}
+
+/// Start serving in the background. `allowed` are the directories which
+/// may be served, besides the site itself.
+pub fn serve(project_path: &Path, site_path: &Path, allowed: Vec<PathBuf>, port: u16, reload: LiveReload) -> io::Result<()> {
+    let listener = try!(TcpListener::bind(("0.0.0.0", port)));
+
+    let project_path = utils::absolute_path(project_path);
+    let site_path    = utils::absolute_path(site_path);
+
+    let root_path = project_path.components()
+        .zip(site_path.components())
+        .take_while(|&(project, site)| project == site)
+        .map(|(component, _)| component.as_os_str())
+        .collect::<PathBuf>();
+
+    let site_url = utils::relative_path(&root_path, &site_path).components()
+        .map(|component| format!("/{}", utf8_percent_encode(&component.as_os_str().to_string_lossy(), FORM_URLENCODED_ENCODE_SET)))
+        .collect::<Vec<String>>()
+        .concat();
+
+    let mut allowed = allowed.iter()
+        .map(|path| utils::absolute_path(path))
+        .collect::<Vec<PathBuf>>();
+    allowed.push(site_path);
+
+    let roots = Arc::new(Roots {
+        root_path: root_path,
+        site_url:  site_url.clone(),
+        allowed:   allowed,
+    });
+
+    println!("Serving on http://localhost:{}{}/index.html", port, site_url);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_)     => continue
+            };
+
+            let roots  = roots.clone();
+            let reload = reload.clone();
+
+            thread::spawn(move || {
+                let _ = handle(stream, &roots, &reload);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// GET and HEAD, the latter gets the same headers without the body.
+fn handle(mut stream: TcpStream, roots: &Roots, reload: &LiveReload) -> io::Result<()> {
+    try!(stream.set_read_timeout(Some(Duration::from_secs(REQUEST_TIMEOUT_SECS))));
+
+    let request_line = {
+        let mut reader = BufReader::new(try!(stream.try_clone()));
+        let mut line   = String::new();
+        try!(reader.read_line(&mut line));
+
+        // Skip the headers, we do not need any of them.
+        loop {
+            let mut header = String::new();
+
+            if try!(reader.read_line(&mut header)) == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        line
+    };
+
+    let parts = request_line.split_whitespace().collect::<Vec<&str>>();
+
+    if parts.len() < 2 || (parts[0] != "GET" && parts[0] != "HEAD") {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Method Not Allowed", false);
+    }
+
+    let head = parts[0] == "HEAD";
+
+    // Drop the query string, eg cache busters.
+    let url_path = parts[1].split('?').next().unwrap_or("/");
+
+    if url_path == LIVE_RELOAD_PATH && !head {
+        return live_reload(&mut stream, reload);
+    }
+
+    if url_path == "/" {
+        let location = format!("{}/index.html", roots.site_url);
+        let header   = format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", location);
+
+        return stream.write_all(header.as_bytes());
+    }
+
+    let file_path = match resolve(roots, url_path) {
+        Some(file_path) => file_path,
+        None            => return respond(&mut stream, "404 Not Found", "text/plain", b"Not Found", head)
+    };
+
+    let mut body = Vec::new();
+    try!(File::open(&file_path).and_then(|mut file| file.read_to_end(&mut body)));
+
+    let content_type = content_type(&file_path);
+
+    if content_type.starts_with("text/html") {
+        body = inject_live_reload(body);
+    }
+
+    respond(&mut stream, "200 OK", content_type, &body, head)
+}
+
+/// `/site/iphone-portrait/index.html -> Project/site/iphone-portrait/index.html`
+/// when the project is the root. `None` when outside of the allowed
+/// directories or not a file. Symlinks are followed before checking, so one
+/// in a category directory cannot point anywhere else on the machine.
+fn resolve(roots: &Roots, url_path: &str) -> Option<PathBuf> {
+    let decoded  = lossy_utf8_percent_decode(url_path.as_bytes());
+    let relative = Path::new(decoded.trim_left_matches('/'));
+
+    let safe = relative.components().all(|component| {
+        match component {
+            Component::Normal(_) => true,
+            _                    => false
+        }
+    });
+
+    if !safe {
+        return None;
+    }
+
+    let mut file_path = roots.root_path.join(relative);
+
+    if utils::is_dir(&file_path) {
+        file_path = file_path.join("index.html");
+    }
+
+    let file_path = match fs::canonicalize(&file_path) {
+        Ok(file_path) => file_path,
+        Err(_)        => return None
+    };
+
+    // The roots too, they may be symlinks themselves or not exist yet.
+    let allowed = roots.allowed.iter()
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .any(|root| file_path.starts_with(&root));
+
+    if !allowed || !utils::is_file(&file_path) {
+        return None;
+    }
+
+    Some(file_path)
+}
+
+/// Server-sent events stream, one `reload` event per rebuild. In between a
+/// `:keepalive` comment is written, once the page is gone that write fails
+/// and the thread ends.
+fn live_reload(stream: &mut TcpStream, reload: &LiveReload) -> io::Result<()> {
+    try!(stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"));
+    try!(stream.flush());
+
+    let mut seen = reload.generation();
+
+    loop {
+        match reload.wait(seen, Duration::from_secs(KEEPALIVE_SECS)) {
+            Some(generation) => {
+                seen = generation;
+                try!(stream.write_all(b"data: reload\n\n"));
+            },
+            None => try!(stream.write_all(b":keepalive\n\n"))
+        }
+
+        try!(stream.flush());
+    }
+}
+
+/// Only the headers for a HEAD request.
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], head: bool) -> io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    );
+
+    try!(stream.write_all(header.as_bytes()));
+
+    if head {
+        return Ok(());
+    }
+
+    stream.write_all(body)
+}
+
+fn inject_live_reload(body: Vec<u8>) -> Vec<u8> {
+    let html = String::from_utf8_lossy(&body).into_owned();
+
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], LIVE_RELOAD_SCRIPT, &html[index..]).into_bytes(),
+        None        => body
+    }
+}
+
+fn content_type(file_path: &Path) -> &'static str {
+    let extension = file_path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .unwrap_or(String::new());
+
+    match &extension[..] {
+        "html"         => "text/html; charset=utf-8",
+        "css"          => "text/css; charset=utf-8",
+        "js"           => "application/javascript; charset=utf-8",
+        "json"         => "application/json; charset=utf-8",
+        "png"          => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif"          => "image/gif",
+        "webp"         => "image/webp",
+        "svg"          => "image/svg+xml",
+        _              => "application/octet-stream"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    use super::{
+        resolve,
+        Roots,
+    };
+
+    /// `site/index.html` and `secret.txt` in a fresh directory, only `site`
+    /// may be served.
+    fn roots(name: &str) -> Roots {
+        let root_path = env::temp_dir().join(format!("mockups-serve-{}", name));
+        let _ = fs::remove_dir_all(&root_path);
+
+        fs::create_dir_all(root_path.join("site")).unwrap();
+        File::create(root_path.join("site").join("index.html")).unwrap();
+        File::create(root_path.join("secret.txt")).unwrap();
+
+        Roots {
+            root_path: root_path.clone(),
+            site_url:  "/site".to_string(),
+            allowed:   vec![root_path.join("site")],
+        }
+    }
+
+    fn index(roots: &Roots) -> Option<PathBuf> {
+        fs::canonicalize(roots.root_path.join("site").join("index.html")).ok()
+    }
+
+    #[test]
+    fn resolves_files_and_directories() {
+        let roots = roots("files");
+
+        assert_eq!(resolve(&roots, "/site/index.html"), index(&roots));
+        assert_eq!(resolve(&roots, "/site/"), index(&roots));
+        assert_eq!(resolve(&roots, "/site/missing.html"), None);
+    }
+
+    #[test]
+    fn rejects_parent_directories() {
+        let roots = roots("parent");
+
+        assert_eq!(resolve(&roots, "/site/../secret.txt"), None);
+        assert_eq!(resolve(&roots, "/site/%2e%2e/secret.txt"), None);
+        assert_eq!(resolve(&roots, "/site/%2E%2E%2Fsecret.txt"), None);
+    }
+
+    #[test]
+    fn rejects_absolute_paths_and_files_outside_of_the_allowed_directories() {
+        let roots      = roots("absolute");
+        let secret_url = format!("/{}", roots.root_path.join("secret.txt").display());
+
+        assert_eq!(resolve(&roots, &secret_url), None);
+        assert_eq!(resolve(&roots, &format!("/{}", secret_url)), None);
+        assert_eq!(resolve(&roots, "/%2Fetc%2Fpasswd"), None);
+        assert_eq!(resolve(&roots, "/secret.txt"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_allowed_directories() {
+        use std::os::unix::fs::symlink;
+
+        let roots = roots("symlink");
+        symlink(roots.root_path.join("secret.txt"), roots.root_path.join("site").join("secret.txt")).unwrap();
+        symlink(roots.root_path.join("site").join("index.html"), roots.root_path.join("site").join("home.html")).unwrap();
+
+        assert_eq!(resolve(&roots, "/site/secret.txt"), None);
+        assert_eq!(resolve(&roots, "/site/home.html"), index(&roots));
+    }
+}
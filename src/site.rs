@@ -1,123 +1,538 @@
 //! Generate HTML static site from the data structure.
 
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::Path;
-use std::io;
 use std::io::Write;
 
-use mustache;
 use mustache::{
     MapBuilder,
+    Template,
     VecBuilder,
 };
 
+use url::percent_encoding::{
+    FORM_URLENCODED_ENCODE_SET,
+    utf8_percent_encode,
+};
+
+use config::{
+    Config,
+    ThumbnailSettings,
+};
+
+use error::{
+    Error,
+    Result,
+};
+
+use flows;
+use flows::Flow;
+use hotspots;
+use hotspots::{
+    Hotspot,
+    Hotspots,
+};
+use images;
+use images::Variant;
+use manifest;
+use manifest::Manifest;
+use notes;
+use notes::Notes;
+
 use structure::{
     Category,
+    Image,
     Section,
 };
 
+use template::Templates;
+use theme;
+use theme::Assets;
+
+use utils;
 use utils::{
     is_file,
+    copy_file,
     create_dir,
     create_file,
 };
 
+use Stats;
+
+/// Writes pages whose content changed since the previous build, or all of
+/// them when forced, and remembers the content hash of all of them for the
+/// manifest.
+struct PageWriter<'a> {
+    site_path: &'a Path,
+    previous:  &'a BTreeMap<String, String>,
+    force:     bool,
+    pages:     BTreeMap<String, String>,
+    written:   usize,
+}
+
+impl<'a> PageWriter<'a> {
+    fn new(site_path: &'a Path, previous: &'a BTreeMap<String, String>, force: bool) -> PageWriter<'a> {
+        PageWriter {
+            site_path: site_path,
+            previous:  previous,
+            force:     force,
+            pages:     BTreeMap::new(),
+            written:   0,
+        }
+    }
+
+    /// Fill in the page with `fill_in` and write it. A page which cannot be
+    /// made ends up in `errors`, the other pages are made nevertheless.
+    fn render<F>(&mut self, page_file: &str, errors: &mut Vec<Error>, fill_in: F) where F: FnOnce(&mut Vec<u8>) -> Result<()> {
+        let mut content = Vec::new();
+
+        let result = match fill_in(&mut content) {
+            Ok(())   => self.write(page_file, content),
+            Err(err) => Err(err)
+        };
+
+        if let Err(err) = result {
+            errors.push(err);
+        }
+    }
+
+    /// `page_file` is relative to the site directory, eg `iphone-portrait/index.html`.
+    fn write(&mut self, page_file: &str, content: Vec<u8>) -> Result<()> {
+        let page_path = self.site_path.join(page_file);
+        let hash      = manifest::hash_bytes(&content);
+        let unchanged = !self.force && self.previous.get(page_file) == Some(&hash) && is_file(&page_path);
+
+        if !unchanged {
+            try!(create_file(&page_path)
+                .and_then(|mut file| file.write_all(&content))
+                .map_err(|err| Error::Write(page_path.clone(), err)));
+
+            self.written += 1;
+        }
+
+        self.pages.insert(page_file.to_string(), hash);
+
+        Ok(())
+    }
+
+    /// Delete pages of the previous build which were not written now,
+    /// eg of a removed section.
+    fn remove_stale(&self) -> usize {
+        self.previous.keys()
+            .filter(|page_file| !self.pages.contains_key(*page_file))
+            .filter(|page_file| fs::remove_file(self.site_path.join(page_file)).is_ok())
+            .count()
+    }
+}
+
 /// Generate the HTML file and directory structure. External assets like
 /// styles, images and JavaScripts need to be embedded in the binary
-/// as they are not available at run-time.
-pub fn generate(project_path: &Path, categories: &Vec<Category>) {
-    let site_path   = project_path.join("site");
+/// as they are not available at run-time. Pages are only written when
+/// their content changed since the previous build. Pages and files which
+/// cannot be written are collected in `errors`.
+pub fn generate(project_path: &Path, config: &Config, categories: &Vec<Category>, manifest: &mut Manifest, errors: &mut Vec<Error>) -> Stats {
+    let site_path   = &config.output;
     let icon_path   = project_path.join("mockups").join("icon.png");
     let icon_exists = &is_file(&icon_path);
+    let project_url = &project_url(project_path, site_path);
 
-    // App name is the name of the directory
-    let app_name = project_path.file_name().unwrap().to_str().unwrap();
+    // The site directory, nothing can be written without it
+    if let Err(err) = create_dir(site_path) {
+        errors.push(err);
+        return Stats::default();
+    }
+
+    // CSS files and images, unless the theme replaces them
+    if let Err(err) = copy_assets(config, site_path) {
+        errors.push(err);
+    }
+
+    // Logo and favicon of the project
+    copy_branding(config, site_path, errors);
+
+    // The theme and the project's own stylesheets and scripts
+    let assets = &theme::copy_assets(config, site_path, errors);
+
+    // Originals and the icon, so the site works on its own
+    if config.self_contained {
+        copy_originals(project_path, config, categories, errors);
+
+        if *icon_exists {
+            if let Err(err) = copy_file(&icon_path, &site_path.join("img").join("app-icon.png")) {
+                errors.push(err);
+            }
+        }
+    }
 
-    // The site directory
-    create_dir(&site_path);
+    // The templates, no page can be made without them
+    let templates = match PageTemplates::compile(config) {
+        Ok(templates) => templates,
+        Err(err)      => {
+            errors.push(err);
+            return Stats::default();
+        }
+    };
 
-    // CSS files and images
-    copy_assets(&site_path);
+    // Links between screens for the prototype pages
+    let hotspots = &hotspots::load(project_path, categories, errors);
+
+    // Markdown notes about sections and screens
+    let notes = &notes::load(project_path, categories, errors);
+
+    let previous   = manifest.pages.clone();
+    let mut writer = PageWriter::new(site_path, &previous, config.force);
 
     // The site/index.html file
-    let site_index_path        = site_path.join("index.html");
-    let site_index_file_result = create_file(&site_index_path);
-    fill_in_site_index_file(site_index_file_result, app_name, icon_exists, categories);
+    writer.render("index.html", errors, |page| {
+        fill_in_site_index_file(page, &templates.site, config, assets, project_url, icon_exists, categories)
+    });
 
     for category in categories.iter() {
         let category_path = site_path.join(category.file.clone());
 
         // The site/iphone-portrait directory
-        create_dir(&category_path);
+        if let Err(err) = create_dir(&category_path) {
+            errors.push(err);
+            continue;
+        }
 
         // The site/iphone-portrait/index.html file
-        let category_index_path        = category_path.join("index.html");
-        let category_index_file_result = create_file(&category_index_path);
-        fill_in_category_index_file(category_index_file_result, app_name, icon_exists, categories, category);
+        writer.render(&format!("{}/index.html", category.file), errors, |page| {
+            fill_in_category_index_file(page, &templates.category, config, assets, project_url, icon_exists, categories, category, hotspots, notes)
+        });
 
         for section in category.sections.iter() {
-            let section_path = category_path.join(section.file.clone());
-
             // The site/iphone-portrait/dashboard.html file
-            let section_file_result = create_file(&section_path);
-            fill_in_section_file(section_file_result, app_name, icon_exists, categories, category, section);
+            writer.render(&format!("{}/{}", category.file, section.file), errors, |page| {
+                fill_in_section_file(page, &templates.section, config, assets, project_url, icon_exists, categories, category, section, notes)
+            });
+        }
+
+        // The site/iphone-portrait/index.prototype.html file, when screens link to each
+        // other. Section pages are `<class>.html` and classes have no dot, so a
+        // section called `prototype` does not overwrite it.
+        if has_prototype(category, hotspots) {
+            writer.render(&format!("{}/index.prototype.html", category.file), errors, |page| {
+                fill_in_prototype_file(page, &templates.prototype, config, assets, project_url, icon_exists, category, hotspots)
+            });
+        }
+    }
+
+    // Flows through the screens, possibly across categories
+    let flows = flows::resolve(config, categories, errors);
+
+    if !flows.is_empty() {
+        // The site/flows directory
+        if let Err(err) = create_dir(&site_path.join("flows")) {
+            errors.push(err);
         }
     }
+
+    for flow in flows.iter() {
+        // The site/flows/onboarding.html file
+        writer.render(&format!("flows/{}.html", flow.slug), errors, |page| {
+            fill_in_flow_file(page, &templates.flow, config, assets, project_url, icon_exists, categories, flow)
+        });
+
+        // The site/flows/onboarding.present.html file
+        writer.render(&format!("flows/{}.present.html", flow.slug), errors, |page| {
+            fill_in_flow_file(page, &templates.presenter, config, assets, project_url, icon_exists, categories, flow)
+        });
+    }
+
+    let removed = writer.remove_stale();
+    let total   = writer.pages.len();
+    let written = writer.written;
+
+    manifest.pages = writer.pages;
+
+    Stats {
+        written:   written,
+        unchanged: total - written,
+        removed:   removed,
+    }
+}
+
+/// Compiled templates of all pages.
+struct PageTemplates {
+    site:      Template,
+    category:  Template,
+    section:   Template,
+    prototype: Template,
+    flow:      Template,
+    presenter: Template,
 }
 
-fn copy_assets(site_path: &Path) {
-    copy_styles_css(&site_path.join("css"));
-    // copy_styles_less(&site_path.join("css"));
-    // copy_less_min_js(&site_path.join("js"));
-    copy_logo_img(&site_path.join("img"));
-    copy_icon_img(&site_path.join("img"));
+impl PageTemplates {
+    /// See the `template` module for where they are looked up.
+    fn compile(config: &Config) -> Result<PageTemplates> {
+        let templates = Templates::new(config);
+
+        Ok(PageTemplates {
+            site:      try!(templates.compile("site.mustache", include_str!("templates/site.mustache"))),
+            category:  try!(templates.compile("category.mustache", include_str!("templates/category.mustache"))),
+            section:   try!(templates.compile("section.mustache", include_str!("templates/section.mustache"))),
+            prototype: try!(templates.compile("prototype.mustache", include_str!("templates/prototype.mustache"))),
+            flow:      try!(templates.compile("flow.mustache", include_str!("templates/flow.mustache"))),
+            presenter: try!(templates.compile("presenter.mustache", include_str!("templates/presenter.mustache"))),
+        })
+    }
 }
 
-fn copy_styles_css(css_path: &Path) {
-    create_dir(css_path);
+fn copy_assets(config: &Config, site_path: &Path) -> Result<()> {
+    let embedded = |url: &str| !theme::overrides(config, url);
 
-    let target_path     = css_path.join("styles.css");
-    let mut target_file = File::create(&target_path).unwrap();
-    let data            = include_str!("css/styles.css");
-    let _               = target_file.write_all(data.as_bytes()).unwrap();
+    if embedded("css/styles.css") {
+        try!(copy_styles_css(&site_path.join("css")));
+    }
+    // try!(copy_styles_less(&site_path.join("css")));
+    // try!(copy_less_min_js(&site_path.join("js")));
+    if embedded("js/mockups.js") {
+        try!(copy_mockups_js(&site_path.join("js")));
+    }
+    if embedded("js/prototype.js") {
+        try!(copy_prototype_js(&site_path.join("js")));
+    }
+    if embedded("js/presenter.js") {
+        try!(copy_presenter_js(&site_path.join("js")));
+    }
+    if embedded("img/logo.png") {
+        try!(copy_logo_img(&site_path.join("img")));
+    }
+    if embedded("img/icon.png") {
+        try!(copy_icon_img(&site_path.join("img")));
+    }
+
+    Ok(())
+}
+
+fn copy_styles_css(css_path: &Path) -> Result<()> {
+    try!(create_dir(css_path));
+
+    let target_path = css_path.join("styles.css");
+    let data        = include_str!("css/styles.css");
+
+    write_asset(&target_path, data.as_bytes())
 }
 
 // For development purposes
-// fn copy_styles_less(css_path: &Path) {
-//     create_dir(css_path);
+// fn copy_styles_less(css_path: &Path) -> Result<()> {
+//     try!(create_dir(css_path));
+//
+//     let target_path = css_path.join("styles.less");
+//     let data        = include_str!("css/styles.less");
 //
-//     let target_path     = css_path.join("styles.less");
-//     let mut target_file = File::create(&target_path).unwrap();
-//     let data            = include_str!("css/styles.less");
-//     let _               = target_file.write_all(data.as_bytes()).unwrap();
+//     write_asset(&target_path, data.as_bytes())
 // }
 
 // For development purposes
-// fn copy_less_min_js(js_path: &Path) {
-//     create_dir(js_path);
+// fn copy_less_min_js(js_path: &Path) -> Result<()> {
+//     try!(create_dir(js_path));
 //
-//     let target_path     = js_path.join("less.min.js");
-//     let mut target_file = File::create(&target_path).unwrap();
-//     let data            = include_str!("js/less.min.js");
-//     let _               = target_file.write_all(data.as_bytes()).unwrap();
+//     let target_path = js_path.join("less.min.js");
+//     let data        = include_str!("js/less.min.js");
+//
+//     write_asset(&target_path, data.as_bytes())
 // }
 
-fn copy_logo_img(img_path: &Path) {
-    create_dir(img_path);
+fn copy_mockups_js(js_path: &Path) -> Result<()> {
+    try!(create_dir(js_path));
+
+    let target_path = js_path.join("mockups.js");
+    let data        = include_str!("js/mockups.js");
+
+    write_asset(&target_path, data.as_bytes())
+}
+
+fn copy_prototype_js(js_path: &Path) -> Result<()> {
+    try!(create_dir(js_path));
+
+    let target_path = js_path.join("prototype.js");
+    let data        = include_str!("js/prototype.js");
+
+    write_asset(&target_path, data.as_bytes())
+}
+
+fn copy_presenter_js(js_path: &Path) -> Result<()> {
+    try!(create_dir(js_path));
+
+    let target_path = js_path.join("presenter.js");
+    let data        = include_str!("js/presenter.js");
+
+    write_asset(&target_path, data.as_bytes())
+}
+
+fn copy_logo_img(img_path: &Path) -> Result<()> {
+    try!(create_dir(img_path));
+
+    let target_path = img_path.join("logo.png");
+    let data        = include_bytes!("img/logo.png");
+
+    write_asset(&target_path, data)
+}
+
+fn copy_icon_img(img_path: &Path) -> Result<()> {
+    try!(create_dir(img_path));
+
+    let target_path = img_path.join("icon.png");
+    let data        = include_bytes!("img/icon.png");
+
+    write_asset(&target_path, data)
+}
+
+fn write_asset(target_path: &Path, data: &[u8]) -> Result<()> {
+    create_file(target_path)
+        .and_then(|mut target_file| target_file.write_all(data))
+        .map_err(|err| Error::Write(target_path.to_path_buf(), err))
+}
+
+/// Custom logo and favicon, into `img` next to the embedded assets.
+fn copy_branding(config: &Config, site_path: &Path, errors: &mut Vec<Error>) {
+    if let Some(ref logo_path) = config.branding.logo {
+        if let Err(err) = copy_file(logo_path, &site_path.join(brand_url("brand-logo", logo_path))) {
+            errors.push(err);
+        }
+    }
+
+    if let Some(ref favicon_path) = config.branding.favicon {
+        if let Err(err) = copy_file(favicon_path, &site_path.join(brand_url("favicon", favicon_path))) {
+            errors.push(err);
+        }
+    }
+}
+
+/// `iphone-portrait/XY-[inbox]-0.png -> site/originals/iphone-portrait/XY-[inbox]-0.png`
+///
+/// Files which are no longer in the project are removed.
+fn copy_originals(project_path: &Path, config: &Config, categories: &Vec<Category>, errors: &mut Vec<Error>) {
+    let originals_path = config.output.join("originals");
+
+    for category in categories.iter() {
+        let category_path = originals_path.join(&category.file);
+
+        if let Err(err) = create_dir(&category_path) {
+            errors.push(err);
+            continue;
+        }
+
+        let mut files = Vec::new();
+
+        for section in category.sections.iter() {
+            for image in section.images.iter() {
+                if let Err(err) = copy_file(&project_path.join(&image.category).join(&image.file), &category_path.join(&image.file)) {
+                    errors.push(err);
+                }
+
+                files.push(image.file.clone());
+            }
+        }
 
-    let target_path     = img_path.join("logo.png");
-    let mut target_file = File::create(&target_path).unwrap();
-    let data            = include_bytes!("img/logo.png");
-    let _               = target_file.write_all(data).unwrap();
+        if let Ok(entries) = fs::read_dir(&category_path) {
+            for entry in entries {
+                if let Ok(entry) = entry {
+                    if !files.iter().any(|file| entry.file_name().to_string_lossy() == *file) {
+                        let _ = fs::remove_file(entry.path());
+                    }
+                }
+            }
+        }
+    }
 }
 
-fn copy_icon_img(img_path: &Path) {
-    create_dir(img_path);
+/// The project directory relative to the site directory, eg `../` for
+/// `site` in the project or `../../Dropbox/Awesome%20Mail%20Client/`.
+pub fn project_url(project_path: &Path, site_path: &Path) -> String {
+    let relative = utils::relative_path(&utils::absolute_path(site_path), &utils::absolute_path(project_path));
 
-    let target_path     = img_path.join("icon.png");
-    let mut target_file = File::create(&target_path).unwrap();
-    let data            = include_bytes!("img/icon.png");
-    let _               = target_file.write_all(data).unwrap();
+    relative.components()
+        .map(|component| format!("{}/", utf8_percent_encode(&component.as_os_str().to_string_lossy(), FORM_URLENCODED_ENCODE_SET)))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+/// Full-size image relative to the site directory, inside of it when the
+/// site is self-contained.
+pub fn original_url(config: &Config, project_url: &str, image: &Image) -> String {
+    if config.self_contained {
+        format!("originals/{}/{}", image.category, image.file_url)
+    } else {
+        format!("{}{}/{}", project_url, image.category, image.file_url)
+    }
+}
+
+/// Logo and favicon relative to the site directory. Custom ones are copied
+/// into `img`, the embedded logo is used otherwise.
+fn logo_url(config: &Config) -> String {
+    match config.branding.logo {
+        Some(ref logo_path) => brand_url("brand-logo", logo_path),
+        None                => "img/logo.png".to_string()
+    }
+}
+
+fn favicon_url(config: &Config) -> Option<String> {
+    config.branding.favicon.as_ref().map(|favicon_path| brand_url("favicon", favicon_path))
+}
+
+/// Eg `img/favicon.ico`, keeping the extension of the source.
+fn brand_url(name: &str, source_path: &Path) -> String {
+    match source_path.extension() {
+        Some(extension) => format!("img/{}.{}", name, extension.to_string_lossy()),
+        None            => format!("img/{}", name)
+    }
+}
+
+/// Company, copyright, logo, favicon and accent colour for the pages.
+/// `root_path` is the site directory relative to the page.
+fn insert_branding(builder: MapBuilder, config: &Config, root_path: &str) -> MapBuilder {
+    let branding = &config.branding;
+    let favicon  = favicon_url(config);
+
+    builder
+        .insert_str("company",       branding.company.clone())
+        .insert_str("copyright",     branding.copyright_line())
+        .insert_bool("has_wordmark", branding.wordmark)
+        .insert_str("logo_src",      format!("{}{}", root_path, logo_url(config)))
+        .insert_bool("has_favicon",  favicon.is_some())
+        .insert_str("favicon_src",   format!("{}{}", root_path, favicon.unwrap_or(String::new())))
+        .insert_bool("has_accent",   branding.accent.is_some())
+        .insert_str("accent",        branding.accent.clone().unwrap_or(String::new()))
+}
+
+/// What the partials need: branding, assets, `root_path` and the flows
+/// for the sidebar, `selected_flow` being the slug of the current one.
+fn layout(config: &Config, assets: &Assets, root_path: &str, selected_flow: Option<&str>) -> MapBuilder {
+    insert_assets(insert_branding(MapBuilder::new(), config, root_path), assets)
+        .insert_str("root_path",  root_path)
+        .insert_bool("has_flows", !config.flows.is_empty())
+        .insert_vec("aside_flows", |_| {
+            aside_flows(config, selected_flow)
+        })
+}
+
+/// Stylesheets and scripts of the theme and the project, relative to the
+/// site directory.
+fn insert_assets(builder: MapBuilder, assets: &Assets) -> MapBuilder {
+    builder
+        .insert_vec("stylesheets", |builder| url_items(builder, &assets.stylesheets))
+        .insert_vec("scripts",     |builder| url_items(builder, &assets.scripts))
+}
+
+fn url_items(mut builder: VecBuilder, urls: &Vec<String>) -> VecBuilder {
+    for url in urls.iter() {
+        builder = builder.push_map(|builder| builder.insert_str("url", url.clone()));
+    }
+
+    builder
+}
+
+/// Project icon relative to the site directory.
+fn icon_url(config: &Config, project_url: &str) -> String {
+    if config.self_contained {
+        "img/app-icon.png".to_string()
+    } else {
+        format!("{}mockups/icon.png", project_url)
+    }
 }
 
 fn aside_categories(categories: &Vec<Category>, selected: Option<String>) -> VecBuilder {
@@ -130,6 +545,7 @@ fn aside_categories(categories: &Vec<Category>, selected: Option<String>) -> Vec
             builder
                 .insert_str("file",      category.file.clone())
                 .insert_str("name",      category.name.clone())
+                .insert_str("family",    category.family.clone())
                 .insert_bool("selected", selected_category == category.name.clone())
         });
     }
@@ -137,57 +553,161 @@ fn aside_categories(categories: &Vec<Category>, selected: Option<String>) -> Vec
     builder
 }
 
+/// `starts_group` is set on the first section of a group, which gets its
+/// heading. `selected` is a section class, names may repeat once overridden.
 fn aside_sections(sections: &Vec<Section>, selected: Option<String>) -> VecBuilder {
     let selected_section = selected.unwrap_or(String::new());
 
-    let mut builder = VecBuilder::new();
+    let mut builder  = VecBuilder::new();
+    let mut previous = None;
 
     for section in sections.iter() {
+        let starts_group = section.group.is_some() && section.group.as_ref() != previous;
+        previous         = section.group.as_ref();
+
+        builder = builder.push_map(|builder| {
+            builder
+                .insert_str("file",          section.file.clone())
+                .insert_str("name",          section.name.clone())
+                .insert_str("class",         section.class.clone())
+                .insert_bool("selected",     selected_section == section.class)
+                .insert_bool("starts_group", starts_group)
+                .insert_str("group",         section.group.clone().unwrap_or(String::new()))
+        });
+    }
+
+    builder
+}
+
+fn aside_flows(config: &Config, selected: Option<&str>) -> VecBuilder {
+    let mut builder = VecBuilder::new();
+
+    for flow in config.flows.iter() {
+        let slug = flow.slug();
+
         builder = builder.push_map(|builder| {
             builder
-                .insert_str("file",      section.file.clone())
-                .insert_str("name",      section.name.clone())
-                .insert_str("class",     section.class.clone())
-                .insert_bool("selected", selected_section == section.name.clone())
+                .insert_str("slug",      slug.clone())
+                .insert_str("slug_url",  utf8_percent_encode(&slug, FORM_URLENCODED_ENCODE_SET))
+                .insert_str("name",      flow.name.clone())
+                .insert_bool("selected", selected == Some(&slug[..]))
+        });
+    }
+
+    builder
+}
+
+/// Images of a section with their thumbnails and notes.
+fn image_items(config: &Config, project_url: &str, section: &Section, settings: &ThumbnailSettings, notes: &Notes) -> VecBuilder {
+    let mut builder = VecBuilder::new();
+
+    for image in section.images.iter() {
+        let image_notes = notes.image(&image.category, &image.file);
+
+        builder = builder.push_map(|builder| {
+            image_fields(builder, config, project_url, image, settings)
+                .insert_str("section_class", section.class.clone())
+                .insert_str("section_name",  section.name.clone())
+                .insert_bool("has_notes",    image_notes.is_some())
+                .insert_str("notes",         image_notes.cloned().unwrap_or(String::new()))
         });
     }
 
     builder
 }
 
+/// One image with its thumbnails, for a page one level below the site
+/// directory. `srcset` uses width descriptors when the dimensions are known
+/// and density descriptors otherwise, `sizes` is the width of the 1x
+/// thumbnail.
+fn image_fields(builder: MapBuilder, config: &Config, project_url: &str, image: &Image, settings: &ThumbnailSettings) -> MapBuilder {
+    let variants = images::variants(settings, image);
+    let thumbs   = variants.iter().filter(|variant| variant.dir == "thumbs").collect::<Vec<&Variant>>();
+    let preview  = variants.iter().find(|variant| variant.dir == "previews");
+    let widths   = thumbs.iter().all(|variant| variant.width.is_some());
+
+    let srcset = thumbs.iter()
+        .map(|variant| {
+            if widths {
+                format!("../{} {}w", variant.url, variant.width.unwrap())
+            } else {
+                format!("../{} {}x", variant.url, variant.density)
+            }
+        })
+        .collect::<Vec<String>>()
+        .connect(", ");
+
+    let sizes = match thumbs[0].width {
+        Some(width) if widths => format!("{}px", width),
+        _                     => String::new()
+    };
+
+    let preview_src = match preview {
+        Some(preview) => format!("../{}", preview.url),
+        None          => String::new()
+    };
+
+    builder
+        .insert_str("category",     image.category.clone())
+        .insert_str("file",         image.file.clone())
+        .insert_str("file_url",     image.file_url.clone())
+        .insert_str("original_src", format!("../{}", original_url(config, project_url, image)))
+        .insert_str("thumb_url",    image.thumb_url.clone())
+        .insert_str("thumb_src",    format!("../{}", thumbs[0].url))
+        .insert_str("srcset",       srcset)
+        .insert_bool("has_sizes",   !sizes.is_empty())
+        .insert_str("sizes",        sizes)
+        .insert_bool("has_preview", preview.is_some())
+        .insert_str("preview_src",  preview_src)
+        .insert("number",           &image.number).unwrap()
+        .insert_str("label",        image.label())
+}
+
 fn fill_in_site_index_file(
-    file_result: io::Result<File>,
-    app_name:    &str,
+    page:        &mut Vec<u8>,
+    template:    &Template,
+    config:      &Config,
+    assets:      &Assets,
+    project_url: &str,
     icon_exists: &bool,
     categories:  &Vec<Category>
-) {
-    let mut file = file_result.unwrap();
-
-    let data = MapBuilder::new()
-        .insert_str("app_name",         app_name.clone())
+) -> Result<()> {
+    let data = layout(config, assets, "", None)
+        .insert_str("app_name",         config.app_name.clone())
         .insert_bool("icon_exists",     icon_exists.clone())
+        .insert_str("icon_src",         icon_url(config, project_url))
+        .insert_bool("has_aside_sections", false)
         .insert_vec("aside_categories", |_| {
             aside_categories(categories, None)
         })
         .build();
 
-    let template = mustache::compile_str(include_str!("templates/site.mustache"));
-    let _        = template.render_data(&mut file, &data);
+    let _ = template.render_data(page, &data);
+
+    Ok(())
 }
 
 fn fill_in_category_index_file(
-    file_result: io::Result<File>,
-    app_name:    &str,
+    page:        &mut Vec<u8>,
+    template:    &Template,
+    config:      &Config,
+    assets:      &Assets,
+    project_url: &str,
     icon_exists: &bool,
     categories:  &Vec<Category>,
-    category:    &Category
-) {
-    let mut file = file_result.unwrap();
+    category:    &Category,
+    hotspots:    &Hotspots,
+    notes:       &Notes
+) -> Result<()> {
+    let settings = config.thumbnail_settings(&category.file);
 
-    let data = MapBuilder::new()
-        .insert_str("app_name",         app_name.clone())
+    let data = layout(config, assets, "../", None)
+        .insert_str("app_name",         config.app_name.clone())
         .insert_bool("icon_exists",     icon_exists.clone())
+        .insert_str("icon_src",         format!("../{}", icon_url(config, project_url)))
         .insert_str("category_name",    category.name.clone())
+        .insert_bool("has_prototype",   has_prototype(category, hotspots))
+        .insert_bool("has_aside_sections", !category.sections.is_empty())
         .insert_vec("aside_categories", |_| {
             aside_categories(categories, Some(category.name.clone()))
         })
@@ -196,23 +716,17 @@ fn fill_in_category_index_file(
         })
         .insert_vec("sections", |mut builder| {
             for section in category.sections.iter() {
+                let section_notes = notes.section(&category.file, &section.class);
+
                 builder = builder.push_map(|builder| {
                     builder
-                        .insert_str("file",  section.file.clone())
-                        .insert_str("name",  section.name.clone())
-                        .insert_str("class", section.class.clone())
-                        .insert_vec("images", |mut builder| {
-                            for image in section.images.iter() {
-                                builder = builder.push_map(|builder| {
-                                    builder
-                                        .insert_str("category", image.category.clone())
-                                        .insert_str("file",     image.file.clone())
-                                        .insert_str("file_url", image.file_url.clone())
-                                        .insert("number",       &image.number).unwrap()
-                                });
-                            }
-
-                            builder
+                        .insert_str("file",       section.file.clone())
+                        .insert_str("name",       section.name.clone())
+                        .insert_str("class",      section.class.clone())
+                        .insert_bool("has_notes", section_notes.is_some())
+                        .insert_str("notes",      section_notes.cloned().unwrap_or(String::new()))
+                        .insert_vec("images", |_| {
+                            image_items(config, project_url, section, &settings, notes)
                         })
                 });
             }
@@ -221,39 +735,126 @@ fn fill_in_category_index_file(
         })
         .build();
 
-    let template = mustache::compile_str(include_str!("templates/category.mustache"));
-    let _        = template.render_data(&mut file, &data);
+    let _ = template.render_data(page, &data);
+
+    Ok(())
 }
 
 fn fill_in_section_file(
-    file_result: io::Result<File>,
-    app_name:    &str,
+    page:        &mut Vec<u8>,
+    template:    &Template,
+    config:      &Config,
+    assets:      &Assets,
+    project_url: &str,
     icon_exists: &bool,
     categories:  &Vec<Category>,
     category:    &Category,
-    section:     &Section
-) {
-    let mut file = file_result.unwrap();
-
-    let data = MapBuilder::new()
-        .insert_str("app_name",      app_name.clone())
+    section:     &Section,
+    notes:       &Notes
+) -> Result<()> {
+    let settings      = config.thumbnail_settings(&category.file);
+    let section_notes = notes.section(&category.file, &section.class);
+
+    let data = layout(config, assets, "../", None)
+        .insert_str("app_name",      config.app_name.clone())
         .insert_bool("icon_exists",  icon_exists.clone())
+        .insert_str("icon_src",      format!("../{}", icon_url(config, project_url)))
         .insert_str("category_name", category.name.clone())
         .insert_str("section_name",  section.name.clone())
+        .insert_bool("has_notes",    section_notes.is_some())
+        .insert_str("notes",         section_notes.cloned().unwrap_or(String::new()))
+        .insert_bool("has_aside_sections", true)
         .insert_vec("aside_categories", |_| {
             aside_categories(categories, Some(category.name.clone()))
         })
         .insert_vec("aside_sections", |_| {
-            aside_sections(&category.sections, Some(section.name.clone()))
+            aside_sections(&category.sections, Some(section.class.clone()))
         })
-        .insert_vec("images", |mut builder| {
-            for image in section.images.iter() {
+        .insert_vec("images", |_| {
+            image_items(config, project_url, section, &settings, notes)
+        })
+        .build();
+
+    let _ = template.render_data(page, &data);
+
+    Ok(())
+}
+
+fn fill_in_prototype_file(
+    page:        &mut Vec<u8>,
+    template:    &Template,
+    config:      &Config,
+    assets:      &Assets,
+    project_url: &str,
+    icon_exists: &bool,
+    category:    &Category,
+    hotspots:    &Hotspots
+) -> Result<()> {
+    let data = layout(config, assets, "../", None)
+        .insert_str("app_name",      config.app_name.clone())
+        .insert_bool("icon_exists",  icon_exists.clone())
+        .insert_str("icon_src",      format!("../{}", icon_url(config, project_url)))
+        .insert_str("category_name", category.name.clone())
+        .insert_vec("screens", |mut builder| {
+            for section in category.sections.iter() {
+                for image in section.images.iter() {
+                    let areas = hotspots.get(&(category.file.clone(), image.file.clone()));
+
+                    builder = builder.push_map(|builder| {
+                        builder
+                            .insert_str("file",         image.file.clone())
+                            .insert_str("original_src", format!("../{}", original_url(config, project_url, image)))
+                            .insert_str("section_name", section.name.clone())
+                            .insert_str("label",        image.label())
+                            .insert_vec("hotspots", |builder| {
+                                hotspot_items(builder, image, areas)
+                            })
+                    });
+                }
+            }
+
+            builder
+        })
+        .build();
+
+    let _ = template.render_data(page, &data);
+
+    Ok(())
+}
+
+/// The storyboard and the presenter of a flow, both in `site/flows`.
+fn fill_in_flow_file(
+    page:        &mut Vec<u8>,
+    template:    &Template,
+    config:      &Config,
+    assets:      &Assets,
+    project_url: &str,
+    icon_exists: &bool,
+    categories:  &Vec<Category>,
+    flow:        &Flow
+) -> Result<()> {
+    let data = layout(config, assets, "../", Some(&flow.slug))
+        .insert_str("app_name",         config.app_name.clone())
+        .insert_bool("icon_exists",     icon_exists.clone())
+        .insert_str("icon_src",         format!("../{}", icon_url(config, project_url)))
+        .insert_str("flow_name",        flow.name.clone())
+        .insert_str("slug",             flow.slug.clone())
+        .insert_str("slug_url",         utf8_percent_encode(&flow.slug, FORM_URLENCODED_ENCODE_SET))
+        .insert("step_count",           &flow.steps.len()).unwrap()
+        .insert_bool("has_aside_sections", false)
+        .insert_vec("aside_categories", |_| {
+            aside_categories(categories, None)
+        })
+        .insert_vec("steps", |mut builder| {
+            for (index, step) in flow.steps.iter().enumerate() {
+                let settings = config.thumbnail_settings(&step.category.file);
+
                 builder = builder.push_map(|builder| {
-                    builder
-                        .insert_str("category", image.category.clone())
-                        .insert_str("file",     image.file.clone())
-                        .insert_str("file_url", image.file_url.clone())
-                        .insert("number",       &image.number).unwrap()
+                    image_fields(builder, config, project_url, step.image, &settings)
+                        .insert("step",              &(index + 1)).unwrap()
+                        .insert_str("category_name", step.category.name.clone())
+                        .insert_str("section_class", step.section.class.clone())
+                        .insert_str("section_name",  step.section.name.clone())
                 });
             }
 
@@ -261,6 +862,38 @@ fn fill_in_section_file(
         })
         .build();
 
-    let template = mustache::compile_str(include_str!("templates/section.mustache"));
-    let _        = template.render_data(&mut file, &data);
+    let _ = template.render_data(page, &data);
+
+    Ok(())
+}
+
+/// Whether any screen of the category links to another one.
+fn has_prototype(category: &Category, hotspots: &Hotspots) -> bool {
+    hotspots.keys().any(|&(ref dir, _)| *dir == category.file)
+}
+
+/// Areas positioned in percent of the image, so they scale with it. Pixels
+/// are used when the dimensions are unknown, eg for SVG without a size.
+fn hotspot_items(mut builder: VecBuilder, image: &Image, areas: Option<&Vec<Hotspot>>) -> VecBuilder {
+    let position = |pixels: u32, size: Option<u32>| {
+        match size {
+            Some(size) if size > 0 => format!("{:.4}%", pixels as f64 * 100.0 / size as f64),
+            _                      => format!("{}px", pixels)
+        }
+    };
+
+    for area in areas.map_or(&[][..], |areas| &areas[..]) {
+        builder = builder.push_map(|builder| {
+            builder
+                .insert_str("to",         area.to.clone())
+                .insert_str("target",     area.target.clone())
+                .insert_str("target_url", utf8_percent_encode(&area.target, FORM_URLENCODED_ENCODE_SET))
+                .insert_str("left",       position(area.x, image.width))
+                .insert_str("top",        position(area.y, image.height))
+                .insert_str("width",      position(area.width, image.width))
+                .insert_str("height",     position(area.height, image.height))
+        });
+    }
+
+    builder
 }
@@ -1,9 +1,10 @@
 //! Read file structure into data-structure.
 //! Currently translates a 2-level structure into a 3-level structure.
 
-use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
 use std::fs;
+use std::fs::File;
 use std::str::FromStr;
 use std::string::ToString;
 use regex::Regex;
@@ -13,60 +14,140 @@ use url::percent_encoding::{
     utf8_percent_encode,
 };
 
+use config::{
+    CategoryConfig,
+    Config,
+};
+use error::{
+    Error,
+    Result,
+};
+use hotspots;
+use images;
+use notes;
 use utils;
 
 /// Eg `iPhone Portrait`, contains sections (which contain images).
 pub struct Category {
-    pub file:     String,
-    pub name:     String,
-    pub sections: Vec<Section>,
+    pub file:        String,
+    pub name:        String,
+    pub order:       i32,
+    pub family:      String,
+    pub orientation: Option<String>,
+    pub sections:    Vec<Section>,
 }
 
-/// Eg `Dashboard`, contains images.
+/// Eg `Dashboard`, contains images. `group` is a heading in the sidebar,
+/// see `SectionConfig`.
 pub struct Section {
     pub file:   String,
     pub name:   String,
     pub class:  String,
+    pub order:  i32,
+    pub group:  Option<String>,
     pub images: Vec<Image>,
 }
 
-/// Eg `XY-[dashboard]-1.png`.
+/// Eg `XY-[dashboard]-1.png`. State, variant and version are only set
+/// when the filename pattern has such named captures. Dimensions are
+/// missing for unreadable images and SVG without a size or `viewBox`.
 pub struct Image {
+    pub category:   String,
+    pub file:       String,
+    pub file_url:   String,
+    pub thumb_file: String,
+    pub thumb_url:  String,
+    pub number:     u32,
+    pub state:      Option<String>,
+    pub variant:    Option<String>,
+    pub version:    Option<String>,
+    pub width:      Option<u32>,
+    pub height:     Option<u32>,
+}
+
+/// A file in a category directory that did not make it into the site.
+pub struct SkippedFile {
     pub category: String,
     pub file:     String,
-    pub file_url: String,
-    pub number:   u8,
+    pub reason:   SkipReason,
+}
+
+pub enum SkipReason {
+    BadPattern,
+    UnsupportedExtension,
+    Unreadable,
+    NestedDirectory,
+    BadFrontMatter(String),
+    Orphan,
 }
 
+/// Files we know how to make thumbnails of.
+static SUPPORTED_EXTENSIONS: [&'static str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "svg"];
+
 impl Category {
-    fn new(file: &str, name: &str) -> Category {
+    fn new(config: &CategoryConfig) -> Category {
         Category {
-            file:     file.to_string(),
-            name:     name.to_string(),
-            sections: Vec::new(),
+            file:        config.dir.clone(),
+            name:        config.name.clone(),
+            order:       config.order.unwrap_or(0),
+            family:      config.family.clone().unwrap_or(String::new()),
+            orientation: config.orientation.clone(),
+            sections:    Vec::new(),
         }
     }
 
     /// This is a poor man's version of HashMap's find_or_insert.
     /// I need additional fields on the datastructure so I went with a struct
     /// instead of a HashMap.
-    fn add_section_image(&mut self, section_file: String, filename: &str, number: u8) {
+    fn add_section_image(&mut self, section_file: String, image: Image) {
         let mut section_file_w_ext = section_file.clone();
         section_file_w_ext.push_str(".html");
 
         for section in self.sections.iter_mut() {
             if section.file == section_file_w_ext {
-                section.images.push(Image::new(self.file.clone(), filename, number));
+                section.add_image(image);
                 return;
             }
         }
 
         let section_name = self.name_from_file(section_file.clone());
         let mut section  = Section::new(section_file_w_ext, section_name, section_file);
-        section.images.push(Image::new(self.file.clone(), filename, number));
+        section.images.push(image);
         self.sections.push(section);
     }
 
+    /// The screen `section/number` refers to, eg `inbox/0`, or the first one
+    /// of the section for just `inbox`. The first state or variant wins when
+    /// there are several with the number.
+    pub fn find_screen(&self, reference: &str) -> Option<(&Section, &Image)> {
+        let mut parts = reference.splitn(2, '/');
+        let class     = parts.next().unwrap_or("");
+
+        let number = match parts.next() {
+            Some(number) => match number.parse::<u32>() {
+                Ok(number) => Some(number),
+                Err(_)     => return None
+            },
+            None => None
+        };
+
+        self.sections.iter()
+            .find(|section| section.class == class)
+            .and_then(|section| {
+                section.images.iter()
+                    .find(|image| number.map_or(true, |number| image.number == number))
+                    .map(|image| (section, image))
+            })
+    }
+
+    fn skip(&self, filename: &str, reason: SkipReason, skipped: &mut Vec<SkippedFile>) {
+        skipped.push(SkippedFile {
+            category: self.file.clone(),
+            file:     filename.to_string(),
+            reason:   reason,
+        });
+    }
+
     /// Take a filename (without extension), eg "new-post", split it by
     /// hyphen ["new", "post"], capitalize words ["New", "Post"], join them
     /// by space "New Post".
@@ -91,85 +172,407 @@ impl Category {
     }
 }
 
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            SkipReason::BadPattern                  => "name does not match the filename pattern",
+            SkipReason::UnsupportedExtension        => "unsupported file extension",
+            SkipReason::Unreadable                  => "cannot be read",
+            SkipReason::NestedDirectory             => "nested directories are not scanned",
+            SkipReason::BadFrontMatter(ref message) => return write!(f, "front matter ignored, {}", message),
+            SkipReason::Orphan                      => "no image or section it belongs to",
+        };
+
+        write!(f, "{}", description)
+    }
+}
+
 impl Section {
     fn new(file: String, name: String, class: String) -> Section {
         Section {
             file:   file,
             name:   name,
             class:  class,
+            order:  0,
+            group:  None,
             images: Vec::new(),
         }
     }
+
+    /// Exports of one screen in several scales, eg `Inbox.png` and
+    /// `Inbox@2x.png`, are one image. The biggest scale is kept, it makes the
+    /// sharpest thumbnails.
+    fn add_image(&mut self, image: Image) {
+        let (unscaled, scale) = scale_suffix(&image.file);
+
+        for other in self.images.iter_mut() {
+            let (other_unscaled, other_scale) = scale_suffix(&other.file);
+
+            if other_unscaled == unscaled {
+                if scale > other_scale {
+                    *other = image;
+                }
+
+                return;
+            }
+        }
+
+        self.images.push(image);
+    }
 }
 
 impl Image {
-    fn new(category: String, file: &str, number: u8) -> Image {
+    fn new(category: String, file: &str, number: u32) -> Image {
+        let thumb_file = images::thumb_file(file);
+
         Image {
-            category: category,
-            file:     file.to_string(),
-            file_url: utf8_percent_encode(file, FORM_URLENCODED_ENCODE_SET),
-            number:   number
+            category:   category,
+            file:       file.to_string(),
+            file_url:   utf8_percent_encode(file, FORM_URLENCODED_ENCODE_SET),
+            thumb_url:  utf8_percent_encode(&thumb_file, FORM_URLENCODED_ENCODE_SET),
+            thumb_file: thumb_file,
+            number:     number,
+            state:      None,
+            variant:    None,
+            version:    None,
+            width:      None,
+            height:     None,
         }
     }
-}
 
-/// Those categories are given and some of them may not be present.
-pub fn read_directories(project_path: &Path, categories: &mut Vec<Category>) {
-    let mut possible_categories = HashMap::new();
+    /// Eg `0`, `0 (empty, dark, v2)`.
+    pub fn label(&self) -> String {
+        let extras = vec![&self.state, &self.variant, &self.version]
+            .into_iter()
+            .filter_map(|extra| extra.clone())
+            .collect::<Vec<String>>();
 
-    possible_categories.insert("iphone-portrait",  "iPhone Portrait");
-    possible_categories.insert("iphone-landscape", "iPhone Landscape");
-    possible_categories.insert("ipad-portrait",    "iPad Portrait");
-    possible_categories.insert("ipad-landscape",   "iPad Landscape");
+        if extras.is_empty() {
+            self.number.to_string()
+        } else {
+            format!("{} ({})", self.number, extras.connect(", "))
+        }
+    }
+}
+
+/// Categories come from the project config, some of them may not be present,
+/// those end up in `missing`. They are sorted by their `order` and then by
+/// name. Files that are not mockups are collected in `skipped`.
+pub fn read_directories(project_path: &Path, config: &Config, categories: &mut Vec<Category>, skipped: &mut Vec<SkippedFile>, missing: &mut Vec<String>) -> Result<()> {
+    let image_regex = try!(Regex::new(&config.pattern).map_err(|err| Error::Pattern(format!("{}", err))));
 
-    for (category_file, category_name) in possible_categories.iter() {
-        let category_path = project_path.join(*category_file);
+    for category_config in config.categories.iter() {
+        let category_path = project_path.join(&category_config.dir);
 
         if !utils::is_dir(&category_path) {
-            println!("{:?} not found", category_path);
+            missing.push(category_config.dir.clone());
             continue;
         }
 
-        let mut category = Category::new(*category_file, *category_name);
+        let mut category = Category::new(category_config);
 
-        read_images(&category_path, &mut category);
+        read_images(&category_path, &image_regex, &mut category, skipped);
+        read_section_settings(&category_path, config, &mut category, skipped);
+        sort_sections(&mut category);
         categories.push(category);
     }
 
-    categories.sort_by(|a, b| a.name.cmp(&b.name) );
+    categories.sort_by(|a, b| (a.order, &a.name).cmp(&(b.order, &b.name)) );
+    skipped.sort_by(|a, b| (&a.category, &a.file).cmp(&(&b.category, &b.file)) );
+
+    Ok(())
+}
+
+/// Name, order and group of the sections from `[sections]` in the config,
+/// overridden by the front matter of the section's notes. Notes with bad
+/// front matter are still shown, their settings end up in `skipped`.
+fn read_section_settings(category_path: &Path, config: &Config, category: &mut Category, skipped: &mut Vec<SkippedFile>) {
+    let mut bad_front_matter = Vec::new();
+
+    for section in category.sections.iter_mut() {
+        let notes_file = notes::section_notes_file(&section.class);
+        let notes_path = category_path.join(&notes_file);
+
+        let front_matter = if utils::is_file(&notes_path) {
+            match notes::front_matter(&notes_path) {
+                Ok(front_matter) => front_matter,
+                Err(err)         => {
+                    let message = match err {
+                        Error::Notes(_, message) => message,
+                        err                      => format!("{}", err),
+                    };
+
+                    bad_front_matter.push((notes_file, message));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        for settings in config.sections.get(&section.class).into_iter().chain(front_matter.iter()) {
+            if let Some(ref name) = settings.name {
+                section.name = name.clone();
+            }
+
+            if let Some(order) = settings.order {
+                section.order = order;
+            }
+
+            if settings.group.is_some() {
+                section.group = settings.group.clone();
+            }
+        }
+    }
+
+    for (notes_file, message) in bad_front_matter.into_iter() {
+        category.skip(&notes_file, SkipReason::BadFrontMatter(message), skipped);
+    }
+}
+
+/// By `order` and then by name, like categories. Sections without a group
+/// go first, those of a group are kept together where the first of them is,
+/// so its heading shows once and nothing ungrouped ends up under it.
+fn sort_sections(category: &mut Category) {
+    category.sections.sort_by(|a, b| (a.order, &a.name).cmp(&(b.order, &b.name)) );
+
+    let mut groups: Vec<Option<String>> = vec![None];
+
+    for section in category.sections.iter() {
+        if !groups.contains(&section.group) {
+            groups.push(section.group.clone());
+        }
+    }
+
+    let position = |group: &Option<String>| groups.iter().position(|other| other == group);
+
+    // Stable, the order within a group stays.
+    category.sections.sort_by(|a, b| position(&a.group).cmp(&position(&b.group)) );
+}
+
+/// `Inbox@2x.png -> ("Inbox.png", 2)`, `Inbox.png -> ("Inbox.png", 1)`.
+fn scale_suffix(file: &str) -> (String, u32) {
+    let (stem, extension) = match file.rfind('.') {
+        Some(index) => (&file[..index], &file[index..]),
+        None        => (file, "")
+    };
+
+    if let Some(index) = stem.rfind('@') {
+        let suffix = &stem[index + 1..];
+
+        if suffix.ends_with('x') {
+            if let Ok(scale) = suffix[..suffix.len() - 1].parse::<u32>() {
+                return (format!("{}{}", &stem[..index], extension), scale);
+            }
+        }
+    }
+
+    (file.to_string(), 1)
+}
+
+/// Files next to the images which describe them, see the `hotspots` and
+/// `notes` modules.
+pub fn is_sidecar(filename: &str) -> bool {
+    filename.ends_with(".hotspots.json") || filename.ends_with(".md")
+}
+
+/// Whether a sidecar file describes a section or an image of the category,
+/// eg not the notes of a renamed screen.
+fn has_owner(category: &Category, sidecar: &str) -> bool {
+    category.sections.iter().any(|section| {
+        notes::section_notes_file(&section.class) == sidecar ||
+            section.images.iter().any(|image| {
+                notes::image_notes_file(&image.file) == sidecar || hotspots::sidecar_file(&image.file) == sidecar
+            })
+    })
+}
+
+/// Print what was skipped and why, grouped by category.
+pub fn report_skipped(skipped: &Vec<SkippedFile>) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    println!("Skipped {} file(s):", skipped.len());
+
+    for file in skipped.iter() {
+        println!("  {}/{}: {}", file.category, file.file, file.reason);
+    }
 }
 
 /// Extract the section name from the image and insert it into the category.
-/// TODO: walk_dir is a) unstable and b) it walks recursively, which is not wanted. Find a way around.
-fn read_images(category_path: &Path, category: &mut Category) {
-    let paths = match fs::walk_dir(&category_path) {
-        Ok(paths) => paths.map(|path| path.unwrap()),
-        Err(_)    => return
+/// Only the category directory itself is read, nested directories are skipped.
+/// Sidecar files without their image or section are skipped too.
+fn read_images(category_path: &Path, image_regex: &Regex, category: &mut Category, skipped: &mut Vec<SkippedFile>) {
+    let entries = match fs::read_dir(&category_path) {
+        Ok(entries) => entries,
+        Err(_)      => return
     };
 
-    let image_regex = Regex::new(r"\A[A-Z]{2}-\[(?P<section>[\w-]+)\]-(?P<number>\d+)\.png\z").unwrap();
+    let mut sidecars = Vec::new();
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_)    => continue
+        };
+
+        let path = entry.path();
+
+        let filename = match entry.file_name().into_string() {
+            Ok(filename) => filename,
+            Err(name)    => {
+                category.skip(&name.to_string_lossy(), SkipReason::Unreadable, skipped);
+                continue;
+            }
+        };
+
+        // .DS_Store and friends
+        if filename.starts_with(".") {
+            continue;
+        }
 
-    for path in paths {
-        if !utils::is_file(&path.path()) {
+        // Belongs to an image or section, eg `XY-[inbox]-0.hotspots.json`
+        if is_sidecar(&filename) {
+            sidecars.push(filename);
             continue;
         }
 
-        let filename = &path.file_name().into_string().unwrap();
+        if utils::is_dir(&path) {
+            category.skip(&filename, SkipReason::NestedDirectory, skipped);
+            continue;
+        }
 
-        match image_regex.captures(filename) {
+        if !utils::is_file(&path) || File::open(&path).is_err() {
+            category.skip(&filename, SkipReason::Unreadable, skipped);
+            continue;
+        }
+
+        if !SUPPORTED_EXTENSIONS.contains(&&images::extension(&path)[..]) {
+            category.skip(&filename, SkipReason::UnsupportedExtension, skipped);
+            continue;
+        }
+
+        match image_regex.captures(&filename) {
             Some(caps) => {
-                let section_file = ToString::to_string(caps.name("section").unwrap());
-                let number: u8   = FromStr::from_str(caps.name("number").unwrap()).unwrap();
+                // The capture may be in an optional group of a custom pattern.
+                let section_file = match caps.name("section") {
+                    Some(section) => section.to_string(),
+                    None          => {
+                        category.skip(&filename, SkipReason::BadPattern, skipped);
+                        continue;
+                    }
+                };
+
+                // Exports like `Inbox@2x.png` have no number, they are the first screen.
+                let number: u32 = match caps.name("number") {
+                    Some(number) => match FromStr::from_str(number) {
+                        Ok(number) => number,
+                        Err(_)     => {
+                            category.skip(&filename, SkipReason::BadPattern, skipped);
+                            continue;
+                        }
+                    },
+                    None => 0
+                };
+
+                let mut image = Image::new(category.file.clone(), &filename, number);
+                image.state   = caps.name("state").map(ToString::to_string);
+                image.variant = caps.name("variant").map(ToString::to_string);
+                image.version = caps.name("version").map(ToString::to_string);
+
+                if let Some((width, height)) = images::dimensions(&path) {
+                    image.width  = Some(width);
+                    image.height = Some(height);
+                }
 
-                category.add_section_image(section_file, filename, number);
+                category.add_section_image(section_file, image);
             },
-            None => ()
+            None => category.skip(&filename, SkipReason::BadPattern, skipped)
         }
     }
 
-    category.sections.sort_by(|a, b| a.name.cmp(&b.name) );
+    for sidecar in sidecars.iter() {
+        if !has_owner(category, sidecar) {
+            category.skip(sidecar, SkipReason::Orphan, skipped);
+        }
+    }
 
     for section in category.sections.iter_mut() {
-        section.images.sort_by(|a, b| a.number.cmp(&b.number) );
+        section.images.sort_by(|a, b| {
+            (a.number, &a.variant, &a.state, &a.version).cmp(&(b.number, &b.variant, &b.state, &b.version))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+
+    use regex::Regex;
+
+    use config::EXPORT_PATTERN;
+
+    use super::{
+        read_images,
+        scale_suffix,
+        Category,
+        SkipReason,
+    };
+
+    #[test]
+    fn scale_suffix_is_split_off() {
+        assert_eq!(scale_suffix("Inbox@2x.png"), ("Inbox.png".to_string(), 2));
+        assert_eq!(scale_suffix("Inbox.png"), ("Inbox.png".to_string(), 1));
+        assert_eq!(scale_suffix("inbox_01@3x.jpg"), ("inbox_01.jpg".to_string(), 3));
+        assert_eq!(scale_suffix("Inbox@2x"), ("Inbox".to_string(), 2));
+    }
+
+    #[test]
+    fn scale_suffix_needs_a_number_and_an_x() {
+        assert_eq!(scale_suffix("Inbox@x.png"), ("Inbox@x.png".to_string(), 1));
+        assert_eq!(scale_suffix("Inbox@2.png"), ("Inbox@2.png".to_string(), 1));
+        assert_eq!(scale_suffix("user@example.png"), ("user@example.png".to_string(), 1));
+    }
+
+    #[test]
+    fn exports_in_several_scales_are_one_screen_and_overflowing_numbers_are_skipped() {
+        let category_path = env::temp_dir().join("mockups-structure-exports");
+        let _ = fs::remove_dir_all(&category_path);
+        fs::create_dir_all(&category_path).unwrap();
+
+        for file in ["Inbox.png", "Inbox@2x.png", "Inbox@3x.png", "feed_1.png", "feed_99999999999.png"].iter() {
+            File::create(category_path.join(file)).unwrap();
+        }
+
+        let mut category = Category {
+            file:        "exports".to_string(),
+            name:        "Exports".to_string(),
+            order:       0,
+            family:      String::new(),
+            orientation: None,
+            sections:    Vec::new(),
+        };
+        let mut skipped = Vec::new();
+
+        read_images(&category_path, &Regex::new(EXPORT_PATTERN).unwrap(), &mut category, &mut skipped);
+
+        let inbox = category.sections.iter().find(|section| section.class == "Inbox").unwrap();
+        assert_eq!(inbox.images.len(), 1);
+        assert_eq!(inbox.images[0].file, "Inbox@3x.png");
+
+        let feed = category.sections.iter().find(|section| section.class == "feed").unwrap();
+        assert_eq!(feed.images.len(), 1);
+        assert_eq!(feed.images[0].number, 1);
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].file, "feed_99999999999.png");
+
+        match skipped[0].reason {
+            SkipReason::BadPattern => (),
+            ref reason             => panic!("skipped because {}", reason)
+        }
     }
 }
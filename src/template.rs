@@ -0,0 +1,251 @@
+//! Page templates. Every template is looked up in the project's templates
+//! directory (`mockups/templates` unless configured otherwise), then in the
+//! theme's and the user's global one, and the embedded one is used last.
+//!
+//! Pages share the `head`, `nav`, `aside` and `footer` partials, included
+//! with eg `{{> nav}}` and looked up the same way as `nav.mustache`. Links
+//! in them start with `{{root_path}}`, the site directory relative to the
+//! page.
+//!
+//! Templates are checked before they are compiled, so a broken override is
+//! reported with its file and line instead of crashing the build.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use mustache;
+use mustache::Template;
+
+use config::Config;
+
+use error::{
+    Error,
+    Result,
+};
+
+use utils;
+
+/// Partials of the embedded templates, overridable like pages.
+static PARTIALS: [(&'static str, &'static str); 4] = [
+    ("head",   include_str!("templates/head.mustache")),
+    ("nav",    include_str!("templates/nav.mustache")),
+    ("aside",  include_str!("templates/aside.mustache")),
+    ("footer", include_str!("templates/footer.mustache")),
+];
+
+/// Partials including partials deeper than this are most likely a cycle.
+static MAX_PARTIAL_DEPTH: usize = 10;
+
+pub struct Templates {
+    dirs: Vec<PathBuf>,
+}
+
+/// Template text and where it comes from, for error messages.
+struct Source {
+    origin: String,
+    text:   String,
+}
+
+/// `{{name}}` from `start` up to `end`, both byte offsets, `tag` is `name`
+/// without the braces and surrounding whitespace.
+struct Tag {
+    start: usize,
+    end:   usize,
+    line:  usize,
+    tag:   String,
+}
+
+impl Templates {
+    pub fn new(config: &Config) -> Templates {
+        let mut dirs = vec![config.templates.clone()];
+
+        if let Some(theme_dir) = config.theme.templates_dir() {
+            dirs.push(theme_dir);
+        }
+
+        if let Some(global_dir) = global_dir() {
+            dirs.push(global_dir);
+        }
+
+        Templates {
+            dirs: dirs,
+        }
+    }
+
+    /// Where overrides are looked for, most important first.
+    pub fn dirs(&self) -> &Vec<PathBuf> {
+        &self.dirs
+    }
+
+    /// Find, check and compile eg `site.mustache`, with its partials
+    /// included. `embedded` is used when there is no override.
+    pub fn compile(&self, name: &str, embedded: &str) -> Result<Template> {
+        let source = try!(self.source(name, embedded));
+        let text   = try!(self.expand(&source, 0));
+
+        Ok(mustache::compile_str(&text))
+    }
+
+    /// The checked text with every `{{> partial}}` replaced by the partial.
+    fn expand(&self, source: &Source, depth: usize) -> Result<String> {
+        try!(check(source));
+
+        let mut text   = String::new();
+        let mut offset = 0;
+
+        for tag in try!(tags(source)) {
+            if !tag.tag.starts_with(">") {
+                continue;
+            }
+
+            let name = tag.tag[1..].trim();
+
+            if depth == MAX_PARTIAL_DEPTH {
+                return Err(template_error(source, tag.line, format!("partial `{}` is nested too deep, does it include itself?", name)));
+            }
+
+            let embedded = PARTIALS.iter()
+                .find(|&&(partial, _)| partial == name)
+                .map_or("", |&(_, embedded)| embedded);
+
+            let file = format!("{}.mustache", name);
+
+            if embedded.is_empty() && !self.dirs.iter().any(|dir| utils::is_file(&dir.join(&file))) {
+                return Err(template_error(source, tag.line, format!("partial `{}` not found, add {} to the templates directory", name, file)));
+            }
+
+            let partial = try!(self.source(&file, embedded));
+
+            text.push_str(&source.text[offset..tag.start]);
+            text.push_str(&try!(self.expand(&partial, depth + 1)));
+            offset = tag.end;
+        }
+
+        text.push_str(&source.text[offset..]);
+
+        Ok(text)
+    }
+
+    fn source(&self, name: &str, embedded: &str) -> Result<Source> {
+        for dir in self.dirs.iter() {
+            let template_path = dir.join(name);
+
+            if utils::is_file(&template_path) {
+                let mut text = String::new();
+
+                try!(File::open(&template_path)
+                    .and_then(|mut file| file.read_to_string(&mut text))
+                    .map_err(|err| Error::Read(template_path.clone(), err)));
+
+                return Ok(Source {
+                    origin: template_path.display().to_string(),
+                    text:   text,
+                });
+            }
+        }
+
+        Ok(Source {
+            origin: format!("embedded {}", name),
+            text:   embedded.to_string(),
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/mockups/templates`, which is
+/// `~/.config/mockups/templates` by default.
+pub fn global_dir() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None      => env::home_dir().map(|home_dir| home_dir.join(".config"))
+    };
+
+    config_dir.map(|dir| dir.join("mockups").join("templates"))
+}
+
+/// Every tag in order, an error for the first one which is not closed.
+fn tags(source: &Source) -> Result<Vec<Tag>> {
+    let text       = &source.text[..];
+    let mut tags   = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = text[offset..].find("{{") {
+        let start = offset + start;
+        let line  = line_number(text, start);
+
+        // `{{{name}}}` is closed by three braces, everything else by two.
+        let (inner, closing) = if text[start + 2..].starts_with("{") {
+            (start + 3, "}}}")
+        } else {
+            (start + 2, "}}")
+        };
+
+        let end = match text[inner..].find(closing) {
+            Some(end) => inner + end,
+            None      => return Err(template_error(source, line, "tag is not closed".to_string()))
+        };
+
+        offset = end + closing.len();
+
+        tags.push(Tag {
+            start: start,
+            end:   offset,
+            line:  line,
+            tag:   text[inner..end].trim().to_string(),
+        });
+    }
+
+    Ok(tags)
+}
+
+/// Unclosed tags and sections, the mistakes the compiler cannot cope with.
+fn check(source: &Source) -> Result<()> {
+    let mut sections = Vec::new();
+
+    for tag in try!(tags(source)) {
+        let line = tag.line;
+        let tag  = &tag.tag[..];
+
+        if tag.starts_with("!") {
+            continue;
+        }
+
+        if tag.starts_with("=") {
+            return Err(template_error(source, line, "changing the delimiters is not supported".to_string()));
+        }
+
+        let name = tag.trim_left_matches(|c| c == '#' || c == '^' || c == '/' || c == '>' || c == '&').trim();
+
+        if name.is_empty() {
+            return Err(template_error(source, line, format!("`{{{{{}}}}}` has no name", tag)));
+        }
+
+        if tag.starts_with("#") || tag.starts_with("^") {
+            sections.push((name.to_string(), line));
+        } else if tag.starts_with("/") {
+            match sections.pop() {
+                Some((ref open, _)) if open == name => (),
+                Some((open, open_line)) => {
+                    return Err(template_error(source, line, format!("`{{{{/{}}}}}` closes `{}` opened on line {}", name, open, open_line)));
+                },
+                None => {
+                    return Err(template_error(source, line, format!("`{{{{/{}}}}}` closes no section", name)));
+                }
+            }
+        }
+    }
+
+    match sections.pop() {
+        Some((name, line)) => Err(template_error(source, line, format!("section `{}` is never closed", name))),
+        None               => Ok(())
+    }
+}
+
+fn template_error(source: &Source, line: usize, message: String) -> Error {
+    Error::Template(source.origin.clone(), line, message)
+}
+
+fn line_number(text: &str, index: usize) -> usize {
+    text[..index].chars().filter(|ch| *ch == '\n').count() + 1
+}
@@ -0,0 +1,210 @@
+//! Themes change the look of the site. A theme is either built in, `light`
+//! (the default), `dark` or `presentation`, or a directory:
+//!
+//! ```
+//! Acme Theme
+//! |- templates
+//! |    +- nav.mustache
+//! |- css
+//! |    +- acme.css
+//! |- js
+//! |    +- acme.js
+//! +- img
+//!      +- acme-logo.png
+//! ```
+//!
+//! Templates in a theme are looked up after the project's, everything else
+//! is copied to the site verbatim. Stylesheets in `css` and scripts in `js`
+//! are included in every page, `css/styles.css` replaces the embedded one,
+//! and so do eg `js/mockups.js` or `img/logo.png`. The project can add its
+//! own stylesheets and scripts on top.
+//!
+//! Theme files are copied, never hard-linked like the originals, as the
+//! site is written to on every build.
+
+use std::fs;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use config::Config;
+use error::Error;
+use utils;
+
+/// Linked by the templates themselves, replacements of them are not
+/// included a second time.
+static LINKED_ASSETS: [&'static str; 4] = ["css/styles.css", "js/mockups.js", "js/prototype.js", "js/presenter.js"];
+
+pub enum Theme {
+    Light,
+    Dark,
+    Presentation,
+    Dir(PathBuf),
+}
+
+/// Stylesheets and scripts to include, relative to the site directory.
+pub struct Assets {
+    pub stylesheets: Vec<String>,
+    pub scripts:     Vec<String>,
+}
+
+impl Theme {
+    /// Built-in themes by name, anything else is a directory relative to the
+    /// project.
+    pub fn parse(project_path: &Path, name: &str) -> Theme {
+        match name {
+            "light"        => Theme::Light,
+            "dark"         => Theme::Dark,
+            "presentation" => Theme::Presentation,
+            _              => Theme::Dir(project_path.join(name)),
+        }
+    }
+
+    /// Template overrides of a theme directory.
+    pub fn templates_dir(&self) -> Option<PathBuf> {
+        match *self {
+            Theme::Dir(ref theme_path) => Some(theme_path.join("templates")),
+            _                          => None
+        }
+    }
+}
+
+/// Whether the theme or the project's own stylesheets and scripts replace
+/// the embedded asset at `url`, eg `css/styles.css`, so it is not written.
+pub fn overrides(config: &Config, url: &str) -> bool {
+    if let Theme::Dir(ref theme_path) = config.theme {
+        if utils::is_file(&theme_path.join(url)) {
+            return true;
+        }
+    }
+
+    let extras = if url.starts_with("css/") {
+        &config.css
+    } else if url.starts_with("js/") {
+        &config.js
+    } else {
+        return false;
+    };
+
+    let file_name = Path::new(url).file_name();
+
+    extras.iter().any(|path| path.file_name() == file_name)
+}
+
+/// Write the theme and the project's own stylesheets and scripts into the
+/// site, after the embedded assets so they can replace them. Files which
+/// cannot be copied are collected in `errors`.
+pub fn copy_assets(config: &Config, site_path: &Path, errors: &mut Vec<Error>) -> Assets {
+    let mut assets = Assets {
+        stylesheets: Vec::new(),
+        scripts:     Vec::new(),
+    };
+
+    let builtin = match config.theme {
+        Theme::Light               => None,
+        Theme::Dark                => Some(("css/theme-dark.css", include_str!("themes/dark.css"))),
+        Theme::Presentation        => Some(("css/theme-presentation.css", include_str!("themes/presentation.css"))),
+        Theme::Dir(ref theme_path) => {
+            copy_dir(theme_path, site_path, &theme_path.join("templates"), errors);
+
+            assets.stylesheets = list_files(&theme_path.join("css"), "css", "css/");
+            assets.scripts     = list_files(&theme_path.join("js"), "js", "js/");
+
+            None
+        }
+    };
+
+    if let Some((url, css)) = builtin {
+        let target_path = site_path.join(url);
+
+        let written = utils::create_dir(&site_path.join("css")).and_then(|_| {
+            utils::create_file(&target_path)
+                .and_then(|mut file| file.write_all(css.as_bytes()))
+                .map_err(|err| Error::Write(target_path.clone(), err))
+        });
+
+        match written {
+            Ok(_)    => assets.stylesheets.push(url.to_string()),
+            Err(err) => errors.push(err)
+        }
+    }
+
+    copy_extras(&config.css, &site_path.join("css"), "css/", &mut assets.stylesheets, errors);
+    copy_extras(&config.js, &site_path.join("js"), "js/", &mut assets.scripts, errors);
+
+    assets.stylesheets.retain(|url| !LINKED_ASSETS.contains(&&url[..]));
+    assets.scripts.retain(|url| !LINKED_ASSETS.contains(&&url[..]));
+
+    assets
+}
+
+/// Everything but `skip`, keeping the structure.
+fn copy_dir(source_dir: &Path, target_dir: &Path, skip: &Path, errors: &mut Vec<Error>) {
+    let entries = match fs::read_dir(source_dir) {
+        Ok(entries) => entries,
+        Err(err)    => {
+            errors.push(Error::Read(source_dir.to_path_buf(), err));
+            return;
+        }
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let source_path = entry.path();
+        let target_path = target_dir.join(entry.file_name());
+
+        if source_path == skip {
+            continue;
+        }
+
+        if utils::is_dir(&source_path) {
+            match utils::create_dir(&target_path) {
+                Ok(_)    => copy_dir(&source_path, &target_path, skip, errors),
+                Err(err) => errors.push(err)
+            }
+        } else if let Err(err) = utils::copy_contents(&source_path, &target_path) {
+            errors.push(err);
+        }
+    }
+}
+
+/// Eg `css/acme.css` for every `.css` file in the directory, by name.
+fn list_files(dir_path: &Path, extension: &str, url_prefix: &str) -> Vec<String> {
+    let mut urls = match fs::read_dir(dir_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| utils::is_file(path) && path.extension().map_or(false, |ext| ext.to_string_lossy() == extension))
+            .map(|path| format!("{}{}", url_prefix, path.file_name().unwrap().to_string_lossy()))
+            .collect::<Vec<String>>(),
+        Err(_) => Vec::new()
+    };
+
+    urls.sort();
+    urls
+}
+
+/// The project's own files, eg `mockups/extra.css -> site/css/extra.css`.
+fn copy_extras(files: &Vec<PathBuf>, target_dir: &Path, url_prefix: &str, urls: &mut Vec<String>, errors: &mut Vec<Error>) {
+    if files.is_empty() {
+        return;
+    }
+
+    if let Err(err) = utils::create_dir(target_dir) {
+        errors.push(err);
+        return;
+    }
+
+    for source_path in files.iter() {
+        let file_name = match source_path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None            => continue
+        };
+
+        match utils::copy_contents(source_path, &target_dir.join(&file_name)) {
+            Ok(_)    => urls.push(format!("{}{}", url_prefix, file_name)),
+            Err(err) => errors.push(err)
+        }
+    }
+}
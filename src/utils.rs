@@ -1,28 +1,107 @@
 //! Just a bunch of cross-module functions.
 
+use std::env;
 use std::io;
 
 use std::fs;
 use std::fs::{
     File
 };
+use std::io::Read;
 
-use std::path::Path;
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+
+use toml;
+
+use error::{
+    Error,
+    Result,
+};
 
-pub fn create_dir(path: &Path) {
+/// Missing parents are created too, eg for `--out ~/Previews/Project`.
+pub fn create_dir(path: &Path) -> Result<()> {
     if is_dir(path) {
-        return;
+        return Ok(());
+    }
+
+    fs::create_dir_all(path).map_err(|err| Error::CreateDir(path.to_path_buf(), err))
+}
+
+/// Hard-link when possible, copy otherwise, eg across file systems.
+/// Targets at least as new as the source with the same size are kept.
+/// Only for files the site never writes to, see `copy_contents`.
+pub fn copy_file(source_path: &Path, target_path: &Path) -> Result<()> {
+    let source = try!(fs::metadata(source_path).map_err(|err| Error::Read(source_path.to_path_buf(), err)));
+
+    if let Ok(target) = fs::metadata(target_path) {
+        if target.len() == source.len() && mtime(&target) >= mtime(&source) {
+            return Ok(());
+        }
+
+        let _ = fs::remove_file(target_path);
+    }
+
+    if fs::hard_link(source_path, target_path).is_err() {
+        try!(fs::copy(source_path, target_path).map_err(|err| Error::Copy(source_path.to_path_buf(), target_path.to_path_buf(), err)));
     }
 
-    let _ = fs::create_dir(path)
-        .ok()
-        .expect(&format!("Cannot create directory: {}", &path.to_str().unwrap()));
+    Ok(())
 }
 
+/// A real copy, for files which may later be replaced by a write into the
+/// site, eg a theme's `css/styles.css`. A hard link would write into the
+/// source then.
+pub fn copy_contents(source_path: &Path, target_path: &Path) -> Result<()> {
+    let _ = fs::remove_file(target_path);
+
+    fs::copy(source_path, target_path)
+        .map(|_| ())
+        .map_err(|err| Error::Copy(source_path.to_path_buf(), target_path.to_path_buf(), err))
+}
+
+/// A new file, never truncating the old one in place as it may be a hard
+/// link to a source file of an earlier build.
 pub fn create_file(path: &Path) -> io::Result<File> {
+    let _ = fs::remove_file(path);
+
     File::create(path)
 }
 
+pub fn read_file(path: &Path) -> Result<String> {
+    let mut contents = String::new();
+
+    try!(File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|err| Error::Read(path.to_path_buf(), err)));
+
+    Ok(contents)
+}
+
+/// What the TOML parser complained about, joined into one message.
+/// `first_line` is the line of the file the parsed text starts at, eg 2
+/// after a `+++` line.
+pub fn toml_errors(parser: &toml::Parser, first_line: usize) -> String {
+    parser.errors.iter()
+        .map(|error| {
+            let (line, col) = parser.to_linecol(error.lo);
+            format!("line {}, column {}: {}", line + first_line, col + 1, error.desc)
+        })
+        .collect::<Vec<String>>()
+        .connect("; ")
+}
+
+/// `XY-[inbox]-0.png -> XY-[inbox]-0`
+pub fn file_stem(file: &str) -> &str {
+    match file.rfind('.') {
+        Some(index) => &file[..index],
+        None        => file
+    }
+}
+
 pub fn is_dir(file_path: &Path) -> bool {
     match fs::metadata(file_path) {
         Ok(metadata) => metadata.is_dir(),
@@ -36,3 +115,62 @@ pub fn is_file(file_path: &Path) -> bool {
         Err(_)       => false
     }
 }
+
+/// `path` made absolute against the current directory, with `.` and `..`
+/// resolved without looking at the file system, so it may not exist yet.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    let path = match env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        Err(_)          => path.to_path_buf()
+    };
+
+    let mut absolute = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir    => (),
+            Component::ParentDir => { absolute.pop(); },
+            component            => absolute.push(component.as_os_str())
+        }
+    }
+
+    absolute
+}
+
+/// How to get from the `from` directory to `to`, both absolute,
+/// eg `/a/b/site` and `/a/c -> ../../c`.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = from.components().collect::<Vec<Component>>();
+    let to   = to.components().collect::<Vec<Component>>();
+
+    let common = from.iter()
+        .zip(to.iter())
+        .take_while(|&(from, to)| from == to)
+        .count();
+
+    let mut relative = PathBuf::new();
+
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+
+    for component in to[common..].iter() {
+        relative.push(component.as_os_str());
+    }
+
+    relative
+}
+
+/// Modification time in seconds. Zero where it is not available, which
+/// makes the build manifest fall back to content hashes.
+#[cfg(unix)]
+pub fn mtime(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.mtime() as u64
+}
+
+#[cfg(not(unix))]
+pub fn mtime(_: &fs::Metadata) -> u64 {
+    0
+}
@@ -0,0 +1,218 @@
+//! Poll the project for changes and rebuild, see `mockups watch`.
+//!
+//! Watched are the files in category directories, `mockups/icon.png`,
+//! `mockups/links.toml`, the config file, the templates directories, the
+//! theme, extra styles and scripts and the branding logo and favicon. Design
+//! tools tend to write several files in a row, so a rebuild only starts once
+//! nothing changed for a while.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::thread;
+
+use config::Config;
+use template::Templates;
+use theme::Theme;
+use utils;
+
+static POLL_INTERVAL_MS: u32 = 500;
+static QUIET_PERIOD_MS:  u32 = 1000;
+
+/// Size and modification time of every watched file.
+type Snapshot = BTreeMap<PathBuf, (u64, u64)>;
+
+/// What changed between two snapshots. `files` are eg
+/// `added iphone-portrait/XY-[inbox]-2.png`.
+pub struct Changes {
+    pub config:    bool,
+    pub icon:      bool,
+    pub templates: bool,
+    pub files:     Vec<String>,
+}
+
+impl Changes {
+    /// Only pages show the icon, thumbnails do not need a look.
+    pub fn only_icon(&self) -> bool {
+        self.icon && !self.config && !self.templates && self.files.is_empty()
+    }
+
+    pub fn print(&self) {
+        if self.config {
+            println!("Changed: config");
+        }
+
+        if self.icon {
+            println!("Changed: icon");
+        }
+
+        if self.templates {
+            println!("Changed: templates");
+        }
+
+        for file in self.files.iter() {
+            println!("Changed: {}", file);
+        }
+    }
+}
+
+/// Rebuild with `build` whenever something changes, until killed.
+/// `load` re-reads the config when it changes, a broken config is reported
+/// by it and the previous one stays in use.
+pub fn watch<L, B>(project_path: &Path, config_path: Option<&Path>, mut config: Config, mut load: L, mut build: B)
+    where L: FnMut() -> Option<Config>,
+          B: FnMut(&Config, &Changes)
+{
+    let mut snapshot = take_snapshot(project_path, config_path, &config);
+
+    loop {
+        thread::sleep_ms(POLL_INTERVAL_MS);
+
+        let mut current = take_snapshot(project_path, config_path, &config);
+
+        if current == snapshot {
+            continue;
+        }
+
+        // Wait until the burst of writes is over.
+        loop {
+            thread::sleep_ms(QUIET_PERIOD_MS);
+
+            let next = take_snapshot(project_path, config_path, &config);
+
+            if next == current {
+                break;
+            }
+
+            current = next;
+        }
+
+        let changes = compare(project_path, config_path, &config, &snapshot, &current);
+        changes.print();
+
+        if changes.config {
+            if let Some(new_config) = load() {
+                config = new_config;
+            }
+        }
+
+        build(&config, &changes);
+
+        snapshot = take_snapshot(project_path, config_path, &config);
+    }
+}
+
+/// Both possible config locations are watched so that a newly created
+/// config file is noticed too.
+fn config_paths(project_path: &Path, config_path: Option<&Path>) -> Vec<PathBuf> {
+    match config_path {
+        Some(path) => vec![path.to_path_buf()],
+        None       => vec![
+            project_path.join("mockups.toml"),
+            project_path.join("mockups").join("mockups.toml"),
+        ]
+    }
+}
+
+fn icon_path(project_path: &Path) -> PathBuf {
+    project_path.join("mockups").join("icon.png")
+}
+
+fn take_snapshot(project_path: &Path, config_path: Option<&Path>, config: &Config) -> Snapshot {
+    let mut snapshot = BTreeMap::new();
+
+    let mut files = config_paths(project_path, config_path);
+    files.push(icon_path(project_path));
+    files.push(project_path.join("mockups").join("links.toml"));
+    files.extend(config.css.iter().cloned());
+    files.extend(config.js.iter().cloned());
+    files.extend(config.branding.logo.iter().cloned());
+    files.extend(config.branding.favicon.iter().cloned());
+
+    for file in files {
+        add_file(&mut snapshot, file);
+    }
+
+    let mut dirs = config.categories.iter()
+        .map(|category| project_path.join(&category.dir))
+        .collect::<Vec<PathBuf>>();
+
+    dirs.extend(Templates::new(config).dirs().iter().cloned());
+
+    if let Theme::Dir(ref theme_path) = config.theme {
+        dirs.push(theme_path.join("css"));
+        dirs.push(theme_path.join("js"));
+        dirs.push(theme_path.join("img"));
+    }
+
+    for dir in dirs {
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for entry in entries {
+                if let Ok(entry) = entry {
+                    add_file(&mut snapshot, entry.path());
+                }
+            }
+        }
+    }
+
+    snapshot
+}
+
+fn add_file(snapshot: &mut Snapshot, path: PathBuf) {
+    if let Ok(metadata) = fs::metadata(&path) {
+        if metadata.is_file() {
+            snapshot.insert(path, (metadata.len(), utils::mtime(&metadata)));
+        }
+    }
+}
+
+fn compare(project_path: &Path, config_path: Option<&Path>, config: &Config, before: &Snapshot, after: &Snapshot) -> Changes {
+    let mut changes = Changes {
+        config:    false,
+        icon:      false,
+        templates: false,
+        files:     Vec::new(),
+    };
+
+    let config_files = config_paths(project_path, config_path);
+    let icon_file    = icon_path(project_path);
+    let templates    = Templates::new(config);
+
+    let mut changed = Vec::new();
+
+    for (path, stat) in after.iter() {
+        match before.get(path) {
+            None                               => changed.push(("added", path)),
+            Some(old_stat) if old_stat != stat => changed.push(("changed", path)),
+            _                                  => ()
+        }
+    }
+
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            changed.push(("removed", path));
+        }
+    }
+
+    for (what, path) in changed {
+        if config_files.contains(path) {
+            changes.config = true;
+        } else if *path == icon_file {
+            changes.icon = true;
+        } else if templates.dirs().iter().any(|templates_path| path.starts_with(templates_path)) {
+            changes.templates = true;
+        } else {
+            let file = match path.parent().and_then(|parent| parent.file_name()) {
+                Some(category) => format!("{}/{}", category.to_string_lossy(), path.file_name().unwrap().to_string_lossy()),
+                None           => path.to_string_lossy().into_owned()
+            };
+
+            changes.files.push(format!("{} {}", what, file));
+        }
+    }
+
+    changes
+}